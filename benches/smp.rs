@@ -0,0 +1,49 @@
+//! Time the Lazy SMP search to a fixed depth on a few positions with 1, 2, 4 and 8 threads, and print the speedup
+//! over one thread.  Run with `cargo bench --bench smp`.
+use chess::{Board, Game};
+use chess_ai::{Agent, SmpAgent};
+use std::str::FromStr;
+use std::time::{Duration, Instant};
+
+const DEPTH: u8 = 5;
+const THREADS: [usize; 4] = [1, 2, 4, 8];
+const POSITIONS: [(&str, &str); 3] = [
+    (
+        "start",
+        "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1",
+    ),
+    (
+        "kiwipete",
+        "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1",
+    ),
+    ("endgame", "8/2p5/3p4/KP5r/1R3p1k/8/4P1P1/8 w - - 0 1"),
+];
+
+/// Search the position with a fresh agent, returning the time taken and the nodes searched.
+fn time_search(fen: &str, threads: usize) -> (Duration, u64) {
+    let mut agent = SmpAgent::new(DEPTH);
+    agent.set_threads(threads);
+    let game = Game::new_with_board(Board::from_str(fen).unwrap());
+    let start = Instant::now();
+    agent.make_move(&game).unwrap();
+    (start.elapsed(), agent.nodes())
+}
+
+fn main() {
+    println!(
+        "{:<10} {:>7} {:>10} {:>12} {:>8}",
+        "position", "threads", "ms", "nodes", "speedup"
+    );
+    for (name, fen) in POSITIONS {
+        let mut base = None;
+        for threads in THREADS {
+            let (elapsed, nodes) = time_search(fen, threads);
+            let base = *base.get_or_insert(elapsed);
+            println!(
+                "{name:<10} {threads:>7} {:>10.1} {nodes:>12} {:>7.2}x",
+                elapsed.as_secs_f64() * 1000.0,
+                base.as_secs_f64() / elapsed.as_secs_f64()
+            );
+        }
+    }
+}
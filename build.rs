@@ -0,0 +1,20 @@
+/// Generate the C header from `src/ffi.rs` alone, into `OUT_DIR` so builds leave the tree alone.  A test checks the
+/// tracked `include/chess_ai.h` matches it; `CHESS_AI_UPDATE_HEADER=1 cargo build` rewrites that copy.
+fn main() {
+    println!("cargo:rerun-if-changed=src/ffi.rs");
+    println!("cargo:rerun-if-changed=cbindgen.toml");
+    println!("cargo:rerun-if-env-changed=CHESS_AI_UPDATE_HEADER");
+
+    let crate_dir = std::env::var("CARGO_MANIFEST_DIR").unwrap();
+    let out_dir = std::env::var("OUT_DIR").unwrap();
+    let config = cbindgen::Config::from_file(format!("{crate_dir}/cbindgen.toml")).unwrap();
+    let header = cbindgen::Builder::new()
+        .with_config(config)
+        .with_src(format!("{crate_dir}/src/ffi.rs"))
+        .generate()
+        .expect("Unable to generate the C header");
+    header.write_to_file(format!("{out_dir}/chess_ai.h"));
+    if std::env::var_os("CHESS_AI_UPDATE_HEADER").is_some() {
+        header.write_to_file(format!("{crate_dir}/include/chess_ai.h"));
+    }
+}
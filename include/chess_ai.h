@@ -0,0 +1,106 @@
+/* Generated by cbindgen from src/ffi.rs with `CHESS_AI_UPDATE_HEADER=1 cargo build`; do not edit. */
+
+#ifndef CHESS_AI_H
+#define CHESS_AI_H
+
+#include <stdarg.h>
+#include <stdbool.h>
+#include <stddef.h>
+#include <stdint.h>
+#include <stdlib.h>
+
+/**
+ * The call succeeded.
+ */
+#define CHESS_AI_OK 0
+
+/**
+ * A pointer was null, a string was not UTF-8, or the agent name is unknown.
+ */
+#define CHESS_AI_INVALID_ARGUMENT -1
+
+/**
+ * The FEN could not be parsed.
+ */
+#define CHESS_AI_INVALID_FEN -2
+
+/**
+ * The position has no legal move.
+ */
+#define CHESS_AI_NO_MOVE -3
+
+/**
+ * The output buffer cannot hold the text and its terminating null.
+ */
+#define CHESS_AI_BUFFER_TOO_SMALL -4
+
+/**
+ * The library failed inside; the agent may be left in a poor state and is best freed.
+ */
+#define CHESS_AI_PANIC -5
+
+/**
+ * An agent created for a C caller, which only ever sees a pointer to it.
+ */
+typedef struct ChessAgent ChessAgent;
+
+#ifdef __cplusplus
+extern "C" {
+#endif // __cplusplus
+
+/**
+ * Create an agent by name ("random", "simple", "depth", "thread", "smp" or "mcts") searching to `depth`.
+ * Returns null if the name is unknown or creating it fails.  Free it with `chess_ai_agent_free`.
+ *
+ * # Safety
+ * `name` must be null or a valid null-terminated string.
+ */
+struct ChessAgent *chess_ai_agent_new(const char *name,
+                                      uint8_t depth);
+
+/**
+ * Destroy an agent from `chess_ai_agent_new`.  Null is ignored.
+ *
+ * # Safety
+ * `agent` must be null or a pointer from `chess_ai_agent_new` that has not already been freed.
+ */
+void chess_ai_agent_free(struct ChessAgent *agent);
+
+/**
+ * Limit each move to `millis` milliseconds, deepening one ply at a time.  Zero goes back to the fixed depth.
+ *
+ * # Safety
+ * `agent` must be null or a live pointer from `chess_ai_agent_new`.
+ */
+int32_t chess_ai_agent_set_time_limit(struct ChessAgent *agent,
+                                      uint64_t millis);
+
+/**
+ * Write the agent's best move for the FEN position into `out` as UCI text (e.g. "e2e4" or "e7e8q").  With a time
+ * limit the agent searches one ply deeper at a time, then goes back to its depth.
+ *
+ * # Safety
+ * `agent` must be null or a live pointer from `chess_ai_agent_new`, `fen` must be null or a valid
+ * null-terminated string, and `out` must be null or point to at least `out_len` writable bytes.
+ */
+int32_t chess_ai_agent_best_move(struct ChessAgent *agent,
+                                 const char *fen,
+                                 char *out,
+                                 size_t out_len);
+
+/**
+ * Write the agent's display name, such as "DepthAgent(3)", into `out`.
+ *
+ * # Safety
+ * `agent` must be null or a live pointer from `chess_ai_agent_new`, and `out` must be null or point to at
+ * least `out_len` writable bytes.
+ */
+int32_t chess_ai_agent_name(const struct ChessAgent *agent,
+                            char *out,
+                            size_t out_len);
+
+#ifdef __cplusplus
+}  // extern "C"
+#endif  // __cplusplus
+
+#endif  /* CHESS_AI_H */
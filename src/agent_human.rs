@@ -0,0 +1,342 @@
+use crate::{Agent, Turn};
+use chess::{Board, ChessMove, Color, MoveGen, Piece, Square};
+use std::io::{BufRead, Write};
+use std::str::FromStr;
+
+/// A chess agent for a person at the terminal: shows the board and reads moves in SAN or UCI notation.
+pub struct HumanAgent<R: BufRead, W: Write> {
+    input: R,
+    output: W,
+    unicode: bool,
+    hint: Option<Box<dyn Agent>>,
+}
+impl<R: BufRead, W: Write> Agent for HumanAgent<R, W> {
+    fn make_move(&mut self, game: &chess::Game) -> Option<chess::ChessMove> {
+        loop {
+            match self.take_turn(game) {
+                Turn::Move(m) | Turn::OfferDraw(m) => return Some(m),
+                Turn::Resign => return None,
+                _ => writeln!(self.output, "Only a move can be played here.").unwrap(),
+            }
+        }
+    }
+
+    fn take_turn(&mut self, game: &chess::Game) -> Turn {
+        let board = game.current_position();
+        if let Some(chess::Action::MakeMove(m)) = game
+            .actions()
+            .iter()
+            .rev()
+            .find(|a| matches!(a, chess::Action::MakeMove(_)))
+        {
+            writeln!(self.output, "Last move: {m}").unwrap();
+        }
+        write!(
+            self.output,
+            "{}",
+            render(&board, board.side_to_move(), self.unicode)
+        )
+        .unwrap();
+
+        let mut offering = false;
+        loop {
+            write!(
+                self.output,
+                "{:?} to move (move, undo, resign, draw, hint, help): ",
+                board.side_to_move()
+            )
+            .unwrap();
+            self.output.flush().unwrap();
+
+            // Treat the end of input as resigning so a scripted game always finishes
+            let mut line = String::new();
+            if self.input.read_line(&mut line).unwrap_or(0) == 0 {
+                writeln!(self.output).unwrap();
+                return Turn::Resign;
+            }
+
+            match line.trim() {
+                "" => {}
+                "resign" => return Turn::Resign,
+                "undo" => {
+                    if game
+                        .actions()
+                        .iter()
+                        .filter(|a| matches!(a, chess::Action::MakeMove(_)))
+                        .count()
+                        < 2
+                    {
+                        writeln!(self.output, "Nothing to undo.").unwrap();
+                    } else {
+                        return Turn::TakeBack;
+                    }
+                }
+                "draw" => {
+                    if game.can_declare_draw() {
+                        return Turn::ClaimDraw;
+                    }
+                    offering = true;
+                    writeln!(self.output, "Enter the move to play with your draw offer.").unwrap();
+                }
+                "hint" => match self.hint.as_mut() {
+                    Some(agent) => match agent.make_move(game) {
+                        Some(m) => writeln!(self.output, "{} suggests {m}", agent.name()).unwrap(),
+                        None => writeln!(self.output, "{} has no suggestion.", agent.name()).unwrap(),
+                    },
+                    None => writeln!(self.output, "No hint agent configured.").unwrap(),
+                },
+                "help" => writeln!(
+                    self.output,
+                    "Enter a move as SAN (Nf3, exd5, O-O, e8=Q) or UCI (g1f3, e7e8q), or one of the commands."
+                )
+                .unwrap(),
+                text => match parse_move(&board, text) {
+                    Some(m) if offering => return Turn::OfferDraw(m),
+                    Some(m) => return Turn::Move(m),
+                    None => writeln!(self.output, "Illegal or unknown move: {text}").unwrap(),
+                },
+            }
+        }
+    }
+
+    fn accept_draw(&mut self, _game: &chess::Game) -> bool {
+        write!(self.output, "Your opponent offers a draw.  Accept (y/n)? ").unwrap();
+        self.output.flush().unwrap();
+
+        let mut line = String::new();
+        self.input.read_line(&mut line).unwrap_or(0);
+        matches!(line.trim(), "y" | "yes")
+    }
+
+    fn name(&self) -> String {
+        "HumanAgent".to_string()
+    }
+}
+impl<R: BufRead, W: Write> HumanAgent<R, W> {
+    /// Create a human player reading from `input` and drawing to `output`, with an optional agent to give hints.
+    pub fn new(input: R, output: W, unicode: bool, hint: Option<Box<dyn Agent>>) -> Self {
+        HumanAgent {
+            input,
+            output,
+            unicode,
+            hint,
+        }
+    }
+
+    /// Everything written so far, for inspecting scripted games.
+    #[cfg(test)]
+    pub fn output(&self) -> &W {
+        &self.output
+    }
+}
+impl HumanAgent<std::io::StdinLock<'static>, std::io::Stdout> {
+    /// Create a human player at the terminal.
+    pub fn stdio(unicode: bool, hint: Option<Box<dyn Agent>>) -> Self {
+        HumanAgent::new(std::io::stdin().lock(), std::io::stdout(), unicode, hint)
+    }
+}
+
+/// Parse a move in UCI or SAN notation, returning it only if it is legal on the board.
+pub fn parse_move(board: &Board, text: &str) -> Option<ChessMove> {
+    let legal = |m: &ChessMove| MoveGen::new_legal(board).any(|l| l == *m);
+    if let Ok(m) = ChessMove::from_str(text) {
+        if legal(&m) {
+            return Some(m);
+        }
+    }
+
+    // The chess crate does not accept check, annotation or "=" promotion markers in SAN
+    let san: String = text
+        .chars()
+        .filter(|c| !matches!(c, '+' | '#' | '!' | '?' | '='))
+        .collect();
+    ChessMove::from_san(board, &san).ok().filter(legal)
+}
+
+/// Draw the board as text, from the point of view of the given side.
+pub fn render(board: &Board, view: Color, unicode: bool) -> String {
+    let mut ranks: Vec<usize> = (0..8).rev().collect();
+    let mut files: Vec<usize> = (0..8).collect();
+    if view == Color::Black {
+        ranks.reverse();
+        files.reverse();
+    }
+
+    let mut text = String::new();
+    for &r in &ranks {
+        text.push_str(&format!("{} ", r + 1));
+        for &f in &files {
+            let sq = Square::make_square(chess::Rank::from_index(r), chess::File::from_index(f));
+            let c = match (board.piece_on(sq), board.color_on(sq)) {
+                (Some(p), Some(c)) => piece_char(p, c, unicode),
+                _ => '.',
+            };
+            text.push(' ');
+            text.push(c);
+        }
+        text.push('\n');
+    }
+    text.push_str("  ");
+    for &f in &files {
+        text.push(' ');
+        text.push((b'a' + u8::try_from(f).unwrap()) as char);
+    }
+    text.push('\n');
+    text
+}
+
+/// The letter (upper case for white) or Unicode figure for a piece.
+fn piece_char(piece: Piece, color: Color, unicode: bool) -> char {
+    let idx = piece.to_index();
+    if unicode {
+        let figures = if color == Color::White {
+            ['♙', '♘', '♗', '♖', '♕', '♔']
+        } else {
+            ['♟', '♞', '♝', '♜', '♛', '♚']
+        };
+        figures[idx]
+    } else {
+        let letter = ['p', 'n', 'b', 'r', 'q', 'k'][idx];
+        if color == Color::White {
+            letter.to_ascii_uppercase()
+        } else {
+            letter
+        }
+    }
+}
+
+//==============================================================================
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::agent_depth::DepthAgent;
+    use crate::agent_simple::SimpleAgent;
+    use crate::score::Score;
+    use crate::threshold::Thresholds;
+    use chess::Game;
+    use std::io::Cursor;
+
+    fn scripted(script: &str) -> HumanAgent<Cursor<Vec<u8>>, Vec<u8>> {
+        HumanAgent::new(
+            Cursor::new(script.as_bytes().to_vec()),
+            Vec::new(),
+            false,
+            Some(Box::new(SimpleAgent::new())),
+        )
+    }
+
+    #[test]
+    fn parse_san_and_uci() {
+        let board = Board::default();
+        let e2e4 = ChessMove::new(Square::E2, Square::E4, None);
+        assert_eq!(parse_move(&board, "e4"), Some(e2e4));
+        assert_eq!(parse_move(&board, "e2e4"), Some(e2e4));
+        assert_eq!(
+            parse_move(&board, "Nf3"),
+            Some(ChessMove::new(Square::G1, Square::F3, None))
+        );
+        assert_eq!(parse_move(&board, "e2e5"), None);
+        assert_eq!(parse_move(&board, "Ke2"), None);
+        assert_eq!(parse_move(&board, "hello"), None);
+
+        let board = Board::from_str("4k3/1P6/8/8/8/8/8/4K3 w - - 0 1").unwrap();
+        let b8q = ChessMove::new(Square::B7, Square::B8, Some(Piece::Queen));
+        assert_eq!(parse_move(&board, "b8=Q+"), Some(b8q));
+        assert_eq!(parse_move(&board, "b7b8q"), Some(b8q));
+    }
+
+    #[test]
+    fn render_board() {
+        let text = render(&Board::default(), Color::White, false);
+        assert!(text.starts_with("8  r n b q k b n r\n"));
+        assert!(text.ends_with("1  R N B Q K B N R\n   a b c d e f g h\n"));
+
+        let text = render(&Board::default(), Color::Black, true);
+        assert!(text.starts_with("1  ♖ ♘ ♗ ♔ ♕ ♗ ♘ ♖\n"));
+        assert!(text.ends_with("   h g f e d c b a\n"));
+    }
+
+    #[test]
+    fn scripted_turns() {
+        let game = Game::new();
+        let mut human = scripted("\nbogus\nundo\nhint\ne4\nresign\n");
+
+        // Bad input is reported and the prompt repeats until a move is given
+        assert_eq!(
+            human.take_turn(&game),
+            Turn::Move(ChessMove::new(Square::E2, Square::E4, None))
+        );
+        let out = String::from_utf8(human.output().clone()).unwrap();
+        assert!(out.contains("Illegal or unknown move: bogus"));
+        assert!(out.contains("Nothing to undo."));
+        assert!(out.contains("SimpleAgent suggests"));
+
+        assert_eq!(human.take_turn(&game), Turn::Resign);
+        assert_eq!(human.take_turn(&game), Turn::Resign);
+    }
+
+    #[test]
+    fn scripted_undo() {
+        let mut game = Game::new();
+        assert!(game.make_move(ChessMove::new(Square::E2, Square::E4, None)));
+        assert!(game.make_move(ChessMove::new(Square::E7, Square::E5, None)));
+        assert_eq!(scripted("undo\n").take_turn(&game), Turn::TakeBack);
+    }
+
+    #[test]
+    fn scripted_draws() {
+        let e2e4 = ChessMove::new(Square::E2, Square::E4, None);
+        assert_eq!(
+            scripted("draw\ne4\n").take_turn(&Game::new()),
+            Turn::OfferDraw(e2e4)
+        );
+        assert!(scripted("y\n").accept_draw(&Game::new()));
+        assert!(!scripted("n\n").accept_draw(&Game::new()));
+
+        // After a repetition the draw is claimed rather than offered
+        let mut game = Game::new();
+        for m in ["Nf3", "Nf6", "Ng1", "Ng8", "Nf3", "Nf6", "Ng1", "Ng8"] {
+            assert!(game.make_move(parse_move(&game.current_position(), m).unwrap()));
+        }
+        assert_eq!(scripted("draw\n").take_turn(&game), Turn::ClaimDraw);
+    }
+
+    #[test]
+    fn play_game_scripted() {
+        // Play, take it back, play something else, then resign as white
+        let human = Box::new(scripted("e4\nundo\nd4\nNf3\nresign\n"));
+        assert_eq!(
+            crate::runner::play_game(human, Box::new(DepthAgent::new(2)), 1),
+            [0, 1, 0]
+        );
+
+        // Running out of input also resigns, here as black
+        let human = Box::new(scripted("e5\n"));
+        assert_eq!(
+            crate::runner::play_game(Box::new(SimpleAgent::new()), human, 1),
+            [1, 0, 0]
+        );
+
+        // In an even position the agent offers a draw, which is accepted
+        let even = Thresholds {
+            resign: None,
+            draw: Some(Score::cp(100)),
+        };
+        let mut agent = DepthAgent::new(1);
+        agent.set_thresholds(even);
+        let human = Box::new(scripted("e4\ny\n"));
+        assert_eq!(
+            crate::runner::play_game(human, Box::new(agent), 1),
+            [0, 0, 1]
+        );
+
+        // Or declined, and then the agent accepts the human's offer
+        let mut agent = DepthAgent::new(1);
+        agent.set_thresholds(even);
+        let human = Box::new(scripted("e4\nn\ndraw\nNf3\n"));
+        assert_eq!(
+            crate::runner::play_game(human, Box::new(agent), 1),
+            [0, 0, 1]
+        );
+    }
+}
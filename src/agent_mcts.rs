@@ -0,0 +1,514 @@
+use crate::eval::Evaluator;
+use crate::info::{InfoCallback, SearchInfo};
+use crate::score::{Calc, Score};
+use crate::threshold::Thresholds;
+use crate::{Agent, Turn};
+use chess::{Board, ChessMove, Color, MoveGen};
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use std::fmt;
+use std::sync::atomic::{AtomicBool, AtomicU32, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+/// UCT exploration constant, close to the theoretical sqrt(2) for results between 0 and 1.
+const EXPLORATION: f64 = 1.4;
+
+/// Playouts stop after this many plies and the piece-square score decides the result.
+const PLAYOUT_PLIES: usize = 40;
+
+/// How much search to do for each move.
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum Budget {
+    /// Run this many playouts.
+    Iterations(u32),
+    /// Run playouts until the time is used up.
+    Time(Duration),
+}
+impl fmt::Display for Budget {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Budget::Iterations(n) => write!(f, "{n} iterations"),
+            Budget::Time(t) => write!(f, "{}ms", t.as_millis()),
+        }
+    }
+}
+
+/// How a playout picks its moves.
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum Playout {
+    /// Play uniformly random moves.
+    Random,
+    /// Mostly play the best piece-square move, as SimpleAgent does, with some random moves to vary the games.
+    Simple,
+}
+
+/// Monte Carlo search node, with results for the side that played its move
+struct Node {
+    the_move: Option<ChessMove>,
+    visits: u32,
+    wins: f64,
+    virtual_loss: u32,
+    untried: Option<Vec<ChessMove>>,
+    children: Vec<Node>,
+}
+impl Node {
+    pub fn new(the_move: Option<ChessMove>) -> Self {
+        Self {
+            the_move,
+            visits: 0,
+            wins: 0.0,
+            virtual_loss: 0,
+            untried: None,
+            children: Vec::new(),
+        }
+    }
+
+    /// The most visited child, which is the most reliable, not the one with the best average from a few playouts.
+    fn most_visited(&self) -> Option<&Node> {
+        self.children.iter().max_by_key(|n| n.visits)
+    }
+
+    /// UCT value of this child, counting playouts still running in other threads as losses.
+    fn uct(&self, parent_visits: u32) -> f64 {
+        let n = f64::from(self.visits + self.virtual_loss);
+        self.wins / n + EXPLORATION * (f64::from(parent_visits.max(1)).ln() / n).sqrt()
+    }
+}
+
+/// A chess agent that runs Monte Carlo tree search with UCT, budgeted by playouts or time.
+/// Threads share the tree, using virtual loss to spread out over different lines.
+pub struct MctsAgent {
+    evaluator: Box<dyn Evaluator>,
+    tree: Option<Node>,
+    root: Option<Board>,
+    budget: Budget,
+    playout: Playout,
+    threads: usize,
+    thresholds: Thresholds,
+    last_score: Option<Score>,
+    rng: StdRng,
+    info: Option<SearchInfo>,
+    callback: Option<InfoCallback>,
+    halt: Arc<AtomicBool>,
+    /// Whether the stop flag ended the last search before its budget.
+    stopped: bool,
+}
+impl Agent for MctsAgent {
+    fn make_move(&mut self, game: &chess::Game) -> Option<ChessMove> {
+        self.search(game).map(|(m, _)| m)
+    }
+
+    fn take_turn(&mut self, game: &chess::Game) -> Turn {
+        let best = self.search(game);
+        self.thresholds.turn(game, best)
+    }
+
+    fn accept_draw(&mut self, _game: &chess::Game) -> bool {
+        self.thresholds.accept(self.last_score)
+    }
+
+    fn name(&self) -> String {
+        match self.playout {
+            Playout::Random => format!("MctsAgent({})", self.budget),
+            Playout::Simple => format!("MctsAgent({}, simple)", self.budget),
+        }
+    }
+
+    /// Playouts have no iterations, so the search is reported once, counting playouts as nodes and taking the depth
+    /// from the line of most visited moves.
+    fn set_info_callback(&mut self, callback: InfoCallback) {
+        self.callback = Some(callback);
+    }
+
+    fn last_info(&self) -> Option<SearchInfo> {
+        self.info.clone()
+    }
+
+    fn analyze(&mut self, game: &chess::Game, lines: usize) -> Vec<SearchInfo> {
+        self.search_lines(game, lines)
+    }
+
+    fn set_stop_flag(&mut self, stop: Arc<AtomicBool>) {
+        self.halt = stop;
+    }
+
+    fn stopped(&self) -> bool {
+        self.stopped
+    }
+}
+impl MctsAgent {
+    pub fn new(budget: Budget) -> Self {
+        MctsAgent {
+            evaluator: Box::new(Calc::new()),
+            tree: None,
+            root: None,
+            budget,
+            playout: Playout::Random,
+            threads: 1,
+            thresholds: Thresholds::default(),
+            last_score: None,
+            rng: StdRng::seed_from_u64(0),
+            info: None,
+            callback: None,
+            halt: Arc::new(AtomicBool::new(false)),
+            stopped: false,
+        }
+    }
+
+    /// Set the scores at which to resign and to offer, accept or claim draws.
+    pub fn set_thresholds(&mut self, thresholds: Thresholds) {
+        self.thresholds = thresholds;
+    }
+
+    /// Choose how playouts pick their moves.
+    pub fn set_playout(&mut self, playout: Playout) {
+        self.playout = playout;
+    }
+
+    /// Run playouts on this many threads sharing one tree.
+    pub fn set_threads(&mut self, threads: usize) {
+        self.threads = threads.max(1);
+    }
+
+    /// Seed the random playouts, for repeatable single threaded games.
+    pub fn set_seed(&mut self, seed: u64) {
+        self.rng = StdRng::seed_from_u64(seed);
+    }
+
+    /// Guide playouts and score their ends with another evaluator than the default `Calc`, starting a new tree.
+    pub fn set_evaluator(&mut self, evaluator: Box<dyn Evaluator>) {
+        self.evaluator = evaluator;
+        self.tree = None;
+        self.root = None;
+    }
+
+    /// Search for the most visited move, returning it with its win rate scaled to a score.
+    fn search(&mut self, game: &chess::Game) -> Option<(ChessMove, Score)> {
+        self.search_lines(game, 1)
+            .first()
+            .map(|info| (info.pv[0], info.score))
+    }
+
+    /// Search, returning the `lines` most visited moves, each with its win rate scaled to a score and the line of most
+    /// visited moves after it.
+    #[allow(clippy::cast_possible_truncation)]
+    fn search_lines(&mut self, game: &chess::Game, lines: usize) -> Vec<SearchInfo> {
+        self.reroot(game);
+
+        let board = game.current_position();
+        let tree = Mutex::new(self.tree.take().unwrap());
+        let (iterations, cut) = (AtomicU32::new(0), AtomicBool::new(false));
+        let start = Instant::now();
+        let seeds: Vec<u64> = (0..self.threads).map(|_| self.rng.gen()).collect();
+        let (evaluator, budget, playout, halt) =
+            (&*self.evaluator, self.budget, self.playout, &*self.halt);
+
+        std::thread::scope(|s| {
+            for seed in seeds {
+                let (tree, iterations, cut) = (&tree, &iterations, &cut);
+                s.spawn(move || {
+                    let mut rng = StdRng::seed_from_u64(seed);
+                    // Always run at least one playout so the root has a move
+                    loop {
+                        MctsAgent::iterate(tree, &board, evaluator, playout, &mut rng);
+                        let count = iterations.fetch_add(1, Ordering::Relaxed) + 1;
+                        let done = match budget {
+                            Budget::Iterations(n) => count >= n,
+                            Budget::Time(t) => start.elapsed() >= t,
+                        };
+                        if done {
+                            break;
+                        }
+                        if halt.load(Ordering::Relaxed) {
+                            cut.store(true, Ordering::Relaxed);
+                            break;
+                        }
+                    }
+                });
+            }
+        });
+        self.tree = Some(tree.into_inner().unwrap());
+        self.stopped = cut.into_inner();
+
+        // Rank the moves as `most_visited` does, the last of equal visits ahead, and follow the most visited moves
+        // after each for its line
+        let tree = self.tree.as_ref().unwrap();
+        let mut ranked: Vec<&Node> = tree.children.iter().collect();
+        ranked.sort_by_key(|n| n.visits);
+        ranked.reverse();
+        let (nodes, elapsed) = (u64::from(iterations.into_inner()), start.elapsed());
+        let infos: Vec<SearchInfo> = ranked
+            .into_iter()
+            .take(lines.max(1))
+            .map(|first| {
+                let mut pv = vec![first.the_move.unwrap()];
+                let mut node = first;
+                while let Some(child) = node.most_visited() {
+                    pv.push(child.the_move.unwrap());
+                    node = child;
+                }
+                let score =
+                    Score::cp(((first.wins / f64::from(first.visits) - 0.5) * 2000.0) as i32);
+                SearchInfo::new(pv.len().min(255) as u8, score, nodes, elapsed, 0, pv)
+            })
+            .collect();
+
+        self.info = infos.first().cloned();
+        if let (Some(info), Some(callback)) = (&self.info, &mut self.callback) {
+            callback(info);
+        }
+        self.last_score = self.info.as_ref().map(|i| i.score);
+        infos
+    }
+
+    /// Reuse the subtree for the current position if the last two moves were searched.
+    fn reroot(&mut self, game: &chess::Game) {
+        let mut new_root = None;
+
+        // Get the last two moves and attempt to find the node (may not exist if new or not yet expanded)
+        let mut moves = game.actions().iter().rev().filter_map(|a| match a {
+            chess::Action::MakeMove(m) => Some(*m),
+            _ => None,
+        });
+        let move2 = moves.next();
+        let move1 = moves.next();
+
+        // Only reuse the tree if it was built for the position two moves ago (not after a take back), or for this
+        // position, as after pondering on it
+        if self.root == Some(game.current_position()) {
+            return;
+        }
+        let continues = match (self.root, move1, move2) {
+            (Some(root), Some(m1), Some(m2)) if root.legal(m1) => {
+                let board = root.make_move_new(m1);
+                board.legal(m2) && board.make_move_new(m2) == game.current_position()
+            }
+            _ => false,
+        };
+
+        if continues {
+            for x in &mut self.tree.as_mut().unwrap().children {
+                if x.the_move == move1 {
+                    if let Some(y) = x.children.iter().position(|n| n.the_move == move2) {
+                        new_root = Some(x.children.swap_remove(y));
+                    }
+                }
+            }
+        }
+
+        self.tree = Some(new_root.unwrap_or_else(|| Node::new(None)));
+        self.root = Some(game.current_position());
+    }
+
+    /// Run one playout: select and expand under the lock, simulate without it, then back up the result.
+    fn iterate(
+        tree: &Mutex<Node>,
+        board: &Board,
+        evaluator: &dyn Evaluator,
+        playout: Playout,
+        rng: &mut StdRng,
+    ) {
+        let (path, leaf) = MctsAgent::select(&mut tree.lock().unwrap(), *board, rng);
+        let result = MctsAgent::simulate(leaf, evaluator, playout, rng);
+        MctsAgent::backpropagate(
+            &mut tree.lock().unwrap(),
+            &path,
+            !board.side_to_move(),
+            result,
+        );
+    }
+
+    /// Walk down by UCT to a node with an untried move and expand it, adding a virtual loss along the path.
+    /// Returns the child indexes taken and the board at the new leaf.
+    fn select(root: &mut Node, mut board: Board, rng: &mut StdRng) -> (Vec<usize>, Board) {
+        let mut path = Vec::new();
+        let mut node = root;
+        node.virtual_loss += 1;
+        loop {
+            let untried = node
+                .untried
+                .get_or_insert_with(|| MoveGen::new_legal(&board).collect());
+            if !untried.is_empty() {
+                let m = untried.swap_remove(rng.gen_range(0..untried.len()));
+                board = board.make_move_new(m);
+                let mut child = Node::new(Some(m));
+                child.virtual_loss = 1;
+                node.children.push(child);
+                path.push(node.children.len() - 1);
+                return (path, board);
+            }
+            if node.children.is_empty() {
+                // Checkmate or stalemate
+                return (path, board);
+            }
+
+            let parent_visits = node.visits + node.virtual_loss;
+            let mut best = 0;
+            for (i, child) in node.children.iter().enumerate() {
+                if child.uct(parent_visits) > node.children[best].uct(parent_visits) {
+                    best = i;
+                }
+            }
+            board = board.make_move_new(node.children[best].the_move.unwrap());
+            path.push(best);
+            node = &mut node.children[best];
+            node.virtual_loss += 1;
+        }
+    }
+
+    /// Play out the game from the board, returning the result for white: 1 for a win, 0 for a loss, 0.5 for a draw.
+    /// Playouts that run out of plies score between 0 and 1 by the piece-square score.
+    fn simulate(
+        mut board: Board,
+        evaluator: &dyn Evaluator,
+        playout: Playout,
+        rng: &mut StdRng,
+    ) -> f64 {
+        for _ in 0..PLAYOUT_PLIES {
+            let mut movegen = MoveGen::new_legal(&board);
+            let m = match (playout, movegen.len()) {
+                (_, 0) if *board.checkers() == chess::EMPTY => return 0.5,
+                (_, 0) if board.side_to_move() == Color::White => return 0.0,
+                (_, 0) => return 1.0,
+                (Playout::Simple, _) if rng.gen_bool(0.8) => {
+                    let is_white = board.side_to_move() == Color::White;
+                    let scored = (
+                        evaluator.score_board(&board, is_white),
+                        evaluator.node_state(&board, is_white),
+                    );
+                    movegen
+                        .max_by_key(|m| {
+                            evaluator
+                                .score_move_with(&board, is_white, *m, scored.0, scored.1)
+                                .0
+                        })
+                        .unwrap()
+                }
+                (_, n) => movegen.nth(rng.gen_range(0..n)).unwrap(),
+            };
+            board = board.make_move_new(m);
+        }
+        0.5 + f64::from(
+            evaluator
+                .score_board(&board, true)
+                .centipawns()
+                .clamp(-500, 500),
+        ) / 1000.0
+    }
+
+    /// Add the result to each node on the path and remove its virtual loss.
+    /// `mover` is the side that played the root's move, and the sides alternate down the path.
+    fn backpropagate(root: &mut Node, path: &[usize], mut mover: Color, result: f64) {
+        let mut node = root;
+        let mut path = path.iter();
+        loop {
+            node.visits += 1;
+            node.virtual_loss -= 1;
+            node.wins += if mover == Color::White {
+                result
+            } else {
+                1.0 - result
+            };
+            match path.next() {
+                Some(&i) => {
+                    node = &mut node.children[i];
+                    mover = !mover;
+                }
+                None => break,
+            }
+        }
+    }
+}
+
+//==============================================================================
+#[cfg(test)]
+mod test {
+    use super::*;
+    use chess::{Game, Square};
+    use more_asserts as ma;
+    use std::str::FromStr;
+
+    /// Check that no virtual loss is left behind and visits add up down the tree
+    fn check_tree(node: &Node) {
+        assert_eq!(node.virtual_loss, 0);
+        let child_visits: u32 = node.children.iter().map(|n| n.visits).sum();
+        assert!(child_visits <= node.visits);
+        node.children.iter().for_each(check_tree);
+    }
+
+    #[test]
+    fn make_two_moves() {
+        let mut game = Game::new();
+
+        let mut white = MctsAgent::new(Budget::Iterations(200));
+        let mut black = MctsAgent::new(Budget::Iterations(200));
+        black.set_playout(Playout::Simple);
+        assert_eq!(white.name(), "MctsAgent(200 iterations)");
+        assert_eq!(black.name(), "MctsAgent(200 iterations, simple)");
+
+        assert!(game.make_move(white.make_move(&game).unwrap()));
+        assert!(game.make_move(black.make_move(&game).unwrap()));
+        assert_eq!(white.tree.as_ref().unwrap().visits, 200);
+        check_tree(white.tree.as_ref().unwrap());
+    }
+
+    #[test]
+    fn mate_in_one() {
+        let board = Board::from_str("6k1/5ppp/8/8/8/8/5PPP/4R1K1 w - - 0 1").unwrap();
+        let mut agent = MctsAgent::new(Budget::Iterations(2000));
+        let (m, score) = agent.search(&Game::new_with_board(board)).unwrap();
+        assert_eq!(m, ChessMove::new(Square::E1, Square::E8, None));
+        assert!(score > Score::cp(500));
+
+        // The line of most visited moves starts with the mate
+        let info = agent.last_info().unwrap();
+        assert_eq!((info.pv[0], info.score, info.nodes), (m, score, 2000));
+        assert_eq!(usize::from(info.depth), info.pv.len());
+
+        // Analysis ranks the mate first, ahead of moves with fewer visits
+        let lines = agent.analyze(&Game::new_with_board(board), 3);
+        assert_eq!(lines.len(), 3);
+        assert_eq!(lines[0].pv[0], m);
+        assert!(lines[1..]
+            .iter()
+            .all(|l| l.pv[0] != m && l.score < lines[0].score));
+    }
+
+    #[test]
+    fn reuse_subtree() {
+        let mut game = Game::new();
+        let mut white = MctsAgent::new(Budget::Iterations(2000));
+        let mut black = MctsAgent::new(Budget::Iterations(10));
+
+        assert!(game.make_move(white.make_move(&game).unwrap()));
+        assert!(game.make_move(black.make_move(&game).unwrap()));
+
+        // The reply was searched, so its playouts carry over to the next search
+        assert!(game.make_move(white.make_move(&game).unwrap()));
+        ma::assert_gt!(white.tree.as_ref().unwrap().visits, 2000);
+
+        // A take back starts again
+        white.reroot(&Game::new());
+        assert_eq!(white.tree.as_ref().unwrap().visits, 0);
+    }
+
+    #[test]
+    fn threads_and_time() {
+        let game = Game::new();
+        let mut agent = MctsAgent::new(Budget::Iterations(1000));
+        agent.set_threads(4);
+        assert!(game
+            .current_position()
+            .legal(agent.make_move(&game).unwrap()));
+        ma::assert_ge!(agent.tree.as_ref().unwrap().visits, 1000);
+        check_tree(agent.tree.as_ref().unwrap());
+
+        // A time budget always runs some playouts
+        let mut agent = MctsAgent::new(Budget::Time(Duration::ZERO));
+        assert_eq!(agent.name(), "MctsAgent(0ms)");
+        assert!(game
+            .current_position()
+            .legal(agent.make_move(&game).unwrap()));
+    }
+}
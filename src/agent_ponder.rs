@@ -0,0 +1,337 @@
+use crate::history::Start;
+use crate::info::{InfoCallback, SearchInfo};
+use crate::{Agent, Turn};
+use chess::{Board, ChessMove, Game};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread::JoinHandle;
+use std::time::Duration;
+
+/// A search running on the opponent's time, from the position after the reply the agent expects.
+struct Ponder {
+    position: Board,
+    thread: JoinHandle<Turn>,
+    /// Stops the search on a miss, or on a hit once the caller's flag is set.
+    stop: Arc<AtomicBool>,
+}
+
+/// Wraps a search agent so it ponders: after each move it keeps searching in a background thread, from the position
+/// after the opponent's expected reply (the second move of its principal variation).  If the opponent plays that reply
+/// (a ponder hit), the background search becomes the real one: the agent waits for it to finish and plays what it
+/// found.  Otherwise (a miss) the background search is stopped and its work is thrown away.
+pub struct PonderAgent {
+    agent: Arc<Mutex<Box<dyn Agent + Send>>>,
+    name: String,
+    /// Whether the agent deepens by itself, kept since asking it would wait for pondering.
+    deepens: bool,
+    /// The caller's stop flag, which the agent searches with when it is not pondering.
+    stop: Arc<AtomicBool>,
+    ponder: Option<Ponder>,
+    /// What a ponder hit decided, until the agent plays it.
+    pondered: Option<(Board, Turn)>,
+    /// What the last search for a move found, which pondering does not overwrite.
+    info: Option<SearchInfo>,
+    /// Whether the stop flag cut that search short.
+    stopped: bool,
+    hits: u32,
+    misses: u32,
+}
+impl Agent for PonderAgent {
+    fn make_move(&mut self, game: &Game) -> Option<ChessMove> {
+        self.settle(game);
+        let the_move = match self.take_pondered(game) {
+            Some(Turn::Move(m) | Turn::OfferDraw(m)) => Some(m),
+            _ => self.agent.lock().unwrap().make_move(game),
+        };
+        self.start(game, the_move);
+        the_move
+    }
+
+    fn take_turn(&mut self, game: &Game) -> Turn {
+        self.settle(game);
+        let turn = match self.take_pondered(game) {
+            Some(turn) => turn,
+            None => self.agent.lock().unwrap().take_turn(game),
+        };
+        if let Turn::Move(m) | Turn::OfferDraw(m) = turn {
+            self.start(game, Some(m));
+        }
+        turn
+    }
+
+    /// Answer from the search after the offer, which a ponder hit has already done.
+    fn accept_draw(&mut self, game: &Game) -> bool {
+        self.settle(game);
+        self.agent.lock().unwrap().accept_draw(game)
+    }
+
+    fn name(&self) -> String {
+        self.name.clone()
+    }
+
+    fn set_info_callback(&mut self, callback: InfoCallback) {
+        self.stop_pondering();
+        self.agent.lock().unwrap().set_info_callback(callback);
+    }
+
+    fn last_info(&self) -> Option<SearchInfo> {
+        self.info.clone()
+    }
+
+    fn analyze(&mut self, game: &Game, lines: usize) -> Vec<SearchInfo> {
+        self.settle(game);
+        self.agent.lock().unwrap().analyze(game, lines)
+    }
+
+    fn set_depth(&mut self, depth: u8) {
+        self.stop_pondering();
+        self.agent.lock().unwrap().set_depth(depth);
+    }
+
+    fn deepens(&self) -> bool {
+        self.deepens
+    }
+
+    /// Stop the searches for moves with the flag, including a pondered search that becomes one on a hit.
+    fn set_stop_flag(&mut self, stop: Arc<AtomicBool>) {
+        self.stop = stop.clone();
+        if self.ponder.is_none() {
+            self.agent.lock().unwrap().set_stop_flag(stop);
+        }
+    }
+
+    fn stopped(&self) -> bool {
+        self.stopped
+    }
+
+    fn set_start(&mut self, start: Start) {
+        self.stop_pondering();
+        self.agent.lock().unwrap().set_start(start);
+    }
+}
+impl PonderAgent {
+    /// Ponder with the agent, which should search to use the time well.
+    pub fn new(mut agent: Box<dyn Agent + Send>) -> Self {
+        let stop = Arc::new(AtomicBool::new(false));
+        agent.set_stop_flag(stop.clone());
+        PonderAgent {
+            name: format!("{}, pondering", agent.name()),
+            deepens: agent.deepens(),
+            agent: Arc::new(Mutex::new(agent)),
+            stop,
+            ponder: None,
+            pondered: None,
+            info: None,
+            stopped: false,
+            hits: 0,
+            misses: 0,
+        }
+    }
+
+    /// How often the opponent played the expected reply, and how often not.
+    pub fn hits_and_misses(&self) -> (u32, u32) {
+        (self.hits, self.misses)
+    }
+
+    /// Ponder on the position after the move and the reply the search expects, if it expects one.
+    fn start(&mut self, game: &Game, the_move: Option<ChessMove>) {
+        let agent = self.agent.lock().unwrap();
+        (self.info, self.stopped) = (agent.last_info(), agent.stopped());
+        drop(agent);
+        let (Some(m), Some(info)) = (the_move, &self.info) else {
+            return;
+        };
+        let Some(&reply) = info.pv.get(1).filter(|_| info.pv[0] == m) else {
+            return;
+        };
+
+        let mut expected = game.clone();
+        if !expected.make_move(m) || !expected.make_move(reply) {
+            return;
+        }
+        let (agent, position, stop) = (
+            self.agent.clone(),
+            expected.current_position(),
+            Arc::<AtomicBool>::default(),
+        );
+        agent.lock().unwrap().set_stop_flag(stop.clone());
+        let thread = std::thread::spawn(move || agent.lock().unwrap().take_turn(&expected));
+        self.ponder = Some(Ponder {
+            position,
+            thread,
+            stop,
+        });
+    }
+
+    /// Before searching the game, let a ponder on its position finish and keep what it decided, or stop one on any
+    /// other.  A finishing ponder still stops when the caller's flag is set.
+    fn settle(&mut self, game: &Game) {
+        let Some(ponder) = &self.ponder else {
+            return;
+        };
+        let position = ponder.position;
+        if position == game.current_position() {
+            self.hits += 1;
+            while !ponder.thread.is_finished() {
+                if self.stop.load(Ordering::Relaxed) {
+                    ponder.stop.store(true, Ordering::Relaxed);
+                }
+                std::thread::sleep(Duration::from_millis(1));
+            }
+            let turn = self.ponder.take().unwrap().thread.join().unwrap();
+            self.pondered = Some((position, turn));
+        } else {
+            self.misses += 1;
+            ponder.stop.store(true, Ordering::Relaxed);
+        }
+        self.wait();
+    }
+
+    /// What a ponder hit decided for the game, instead of searching it again.
+    fn take_pondered(&mut self, game: &Game) -> Option<Turn> {
+        match self.pondered.take() {
+            Some((position, turn)) if position == game.current_position() => Some(turn),
+            _ => None,
+        }
+    }
+
+    fn stop_pondering(&mut self) {
+        if let Some(ponder) = &self.ponder {
+            ponder.stop.store(true, Ordering::Relaxed);
+        }
+        self.wait();
+    }
+
+    /// Wait for the background search, then let the next search run with the caller's flag.
+    fn wait(&mut self) {
+        if let Some(ponder) = self.ponder.take() {
+            ponder.thread.join().unwrap();
+        }
+        self.agent.lock().unwrap().set_stop_flag(self.stop.clone());
+    }
+}
+impl Drop for PonderAgent {
+    fn drop(&mut self) {
+        self.stop_pondering();
+    }
+}
+
+//==============================================================================
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::agent_depth::DepthAgent;
+    use crate::agent_random::RandomAgent;
+    use crate::agent_smp::SmpAgent;
+    use crate::limit::{choose_move_with, Limit};
+    use crate::runner::play_game;
+
+    /// Play the agent's move and then the reply
+    fn play(
+        game: &mut Game,
+        agent: &mut PonderAgent,
+        reply: impl Fn(&SearchInfo) -> Option<ChessMove>,
+    ) {
+        let m = agent.make_move(game).unwrap();
+        assert!(game.make_move(m));
+        let reply = reply(&agent.last_info().unwrap()).unwrap();
+        assert!(game.make_move(reply));
+    }
+
+    #[test]
+    fn hits_and_misses() {
+        for inner in [
+            Box::new(DepthAgent::new(3)) as Box<dyn Agent + Send>,
+            Box::new(SmpAgent::new(3)),
+        ] {
+            let mut game = Game::new();
+            let mut agent = PonderAgent::new(inner);
+            assert!(agent.name().ends_with(", pondering"));
+
+            // The expected reply is a hit, and the search agrees with what pondering found
+            play(&mut game, &mut agent, |info| info.pv.get(1).copied());
+            let m = agent.make_move(&game).unwrap();
+            assert_eq!(agent.hits_and_misses(), (1, 0));
+            assert_eq!(agent.last_info().unwrap().pv[0], m);
+
+            // Any other reply is a miss
+            assert!(game.make_move(m));
+            let expected = agent.last_info().unwrap().pv[1];
+            let other = chess::MoveGen::new_legal(&game.current_position())
+                .find(|r| *r != expected)
+                .unwrap();
+            assert!(game.make_move(other));
+            assert!(game
+                .current_position()
+                .legal(agent.make_move(&game).unwrap()));
+            assert_eq!(agent.hits_and_misses(), (1, 1));
+        }
+    }
+
+    /// Records the position of every search, to see which ones are repeated.
+    struct Recorder {
+        agent: DepthAgent,
+        searched: Arc<Mutex<Vec<Board>>>,
+    }
+    impl Agent for Recorder {
+        fn make_move(&mut self, game: &Game) -> Option<ChessMove> {
+            self.searched.lock().unwrap().push(game.current_position());
+            self.agent.make_move(game)
+        }
+
+        fn name(&self) -> String {
+            self.agent.name()
+        }
+
+        fn last_info(&self) -> Option<SearchInfo> {
+            self.agent.last_info()
+        }
+
+        fn set_stop_flag(&mut self, stop: Arc<AtomicBool>) {
+            self.agent.set_stop_flag(stop);
+        }
+    }
+
+    #[test]
+    fn hit_plays_the_pondered_search() {
+        let searched = Arc::new(Mutex::new(Vec::new()));
+        let recorder = Recorder {
+            agent: DepthAgent::new(3),
+            searched: searched.clone(),
+        };
+        let mut agent = PonderAgent::new(Box::new(recorder));
+        let mut game = Game::new();
+        play(&mut game, &mut agent, |info| info.pv.get(1).copied());
+        let m = agent.make_move(&game).unwrap();
+        assert!(game.current_position().legal(m));
+        assert_eq!(agent.last_info().unwrap().pv[0], m);
+
+        // The position after the hit was searched once, while pondering
+        drop(agent);
+        let searched = searched.lock().unwrap();
+        assert_eq!(
+            searched
+                .iter()
+                .filter(|b| **b == game.current_position())
+                .count(),
+            1
+        );
+    }
+
+    #[test]
+    fn time_limit() {
+        // The deadline reaches the wrapped search, whose fourth ply would take seconds
+        let mut agent = PonderAgent::new(Box::new(DepthAgent::new(1)));
+        let (start, budget) = (std::time::Instant::now(), Duration::from_secs(1));
+        let choice = choose_move_with(&mut agent, &Game::new(), Limit::Time(budget));
+        assert!(start.elapsed() < budget * 3 / 2, "{:?}", start.elapsed());
+        assert!(Board::default().legal(choice.the_move.unwrap()));
+    }
+
+    #[test]
+    fn match_runner() {
+        let agent = PonderAgent::new(Box::new(DepthAgent::new(2)));
+        let wins = play_game(Box::new(agent), Box::new(RandomAgent::new()), 1);
+        assert_eq!(wins.iter().sum::<u32>(), 1);
+    }
+}
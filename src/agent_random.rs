@@ -22,6 +22,11 @@ impl RandomAgent {
         }
     }
 }
+impl Default for RandomAgent {
+    fn default() -> Self {
+        Self::new()
+    }
+}
 
 //==============================================================================
 #[cfg(test)]
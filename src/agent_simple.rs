@@ -1,24 +1,33 @@
-use crate::score::Calc;
+use crate::eval::Evaluator;
+use crate::score::{Calc, Score};
+use crate::see::best_capture;
 use crate::Agent;
 use chess::{BoardStatus, Color, MoveGen};
 
 /// A chess agent that looks at every possible next move, and selects the best based on a piece-square scoring function.
 pub struct SimpleAgent {
-    calc: Calc,
+    evaluator: Box<dyn Evaluator>,
+    /// Count what the opponent could then win by a capture against each move.
+    see: bool,
 }
 impl Agent for SimpleAgent {
     fn make_move(&mut self, game: &chess::Game) -> Option<chess::ChessMove> {
         let board = game.current_position();
         let movegen = MoveGen::new_legal(&board);
-        let mut score = -1000;
+        let mut score = -Score::INFINITY;
         let mut the_move = Option::None;
 
         for m in movegen {
             let b = board.make_move_new(m);
             let s = if b.status() == BoardStatus::Checkmate {
-                1000
+                Score::mate_in(1)
+            } else if self.see {
+                self.evaluator
+                    .score_board(&b, board.side_to_move() == Color::White)
+                    - best_capture(&b)
             } else {
-                self.calc.score_board(&b, board.side_to_move() == Color::White)
+                self.evaluator
+                    .score_board(&b, board.side_to_move() == Color::White)
             };
             if s > score {
                 score = s;
@@ -29,23 +38,46 @@ impl Agent for SimpleAgent {
     }
 
     fn name(&self) -> String {
-        "SimpleAgent".to_string()
+        if self.see {
+            "SimpleAgent(see)".to_string()
+        } else {
+            "SimpleAgent".to_string()
+        }
     }
 }
 impl SimpleAgent {
     pub fn new() -> Self {
         SimpleAgent {
-            calc: Calc::new(),
+            evaluator: Box::new(Calc::new()),
+            see: false,
         }
     }
+
+    /// Avoid hanging pieces: take off each move's score the most the opponent could then win with a capture, by
+    /// static exchange evaluation.
+    pub fn set_see(&mut self, see: bool) {
+        self.see = see;
+    }
+
+    /// Score moves with another evaluator than the default `Calc`.
+    pub fn set_evaluator(&mut self, evaluator: Box<dyn Evaluator>) {
+        self.evaluator = evaluator;
+    }
+}
+impl Default for SimpleAgent {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 //==============================================================================
 #[cfg(test)]
 mod test {
     use super::*;
-    use chess::Game;
+    use crate::eval::Material;
+    use chess::{Board, ChessMove, Game};
     use more_asserts as ma;
+    use std::str::FromStr;
 
     #[test]
     fn make_two_moves() {
@@ -64,4 +96,34 @@ mod test {
         assert!(game.make_move(black.make_move(&game).unwrap()));
         ma::assert_le!(s, calc.score_board(&game.current_position(), false));
     }
+
+    #[test]
+    fn hanging_pieces() {
+        // The knight's best square is guarded by a pawn, so only a careful agent keeps it away
+        let game =
+            Game::new_with_board(Board::from_str("4k3/8/8/4p3/8/8/4N3/4K3 w - - 0 1").unwrap());
+        let mut agent = SimpleAgent::new();
+        assert_eq!(
+            agent.make_move(&game),
+            Some(ChessMove::from_str("e2d4").unwrap())
+        );
+
+        agent.set_see(true);
+        assert_eq!(agent.name(), "SimpleAgent(see)");
+        let m = agent.make_move(&game).unwrap();
+        assert!(!matches!(m.to_string().as_str(), "e2d4" | "e2f4"));
+    }
+
+    #[test]
+    fn evaluator() {
+        // Counting only material, the agent still takes the free knight
+        let game =
+            Game::new_with_board(Board::from_str("4k3/8/8/3n4/8/8/8/3RK3 w - - 0 1").unwrap());
+        let mut agent = SimpleAgent::new();
+        agent.set_evaluator(Box::new(Material));
+        assert_eq!(
+            agent.make_move(&game),
+            Some(ChessMove::from_str("d1d5").unwrap())
+        );
+    }
 }
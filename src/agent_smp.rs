@@ -0,0 +1,1264 @@
+use crate::eval::Evaluator;
+use crate::history::{History, Start};
+use crate::info::{InfoCallback, SearchInfo};
+use crate::score::{Calc, Score, MAX_PLY};
+use crate::see::see;
+use crate::terms::Weights;
+use crate::threshold::Thresholds;
+use crate::tt::{Bound, Entry, TranspositionTable};
+use crate::{Agent, Turn};
+use chess::{Board, ChessMove, Color, MoveGen, Piece, Rank, Square};
+use std::fmt;
+use std::ops::AddAssign;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Instant;
+
+/// How often, in nodes, a thread checks whether another has finished.
+const STOP_CHECK: u64 = 1024;
+/// Widest aspiration window, in centipawns each side, before searching without one.
+const MAX_WINDOW: i32 = 1000;
+
+/// The refinements of `SmpAgent`'s alpha-beta search: selective parts that skip or shorten lines unlikely to matter,
+/// and narrow windows that prove more cutoffs.  Each can be turned off or tuned on its own, to measure what it gains.
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub struct Selectivity {
+    /// Null-move pruning: pass the turn, and if a shallower search still fails high, assume a real move would too.
+    /// Never in check, nor with only pawns left, where having to move can be what loses (zugzwang).
+    pub null_move: bool,
+    /// Plies taken off the search after passing, besides the pass itself.
+    pub null_reduction: u8,
+    /// Late move reductions: search quiet moves late in the order less deeply, and again at full depth if that beats
+    /// the best score so far.
+    pub late_move_reductions: bool,
+    /// Moves searched at full depth before reducing.
+    pub full_depth_moves: usize,
+    /// Least depth left at which moves are reduced.
+    pub reduction_limit: u8,
+    /// Plies taken off a late move.
+    pub reduction: u8,
+    /// Principal variation search: only the first move at a node gets the full window, and the rest a null window
+    /// that just shows they are no better, searching again with the full window those that are.
+    pub principal_variation_search: bool,
+    /// Aspiration windows: search each iteration from the root within this much of the last iteration's score,
+    /// widening the side it falls outside and searching again.  When not set, every iteration uses the full window.
+    pub aspiration_window: Option<Score>,
+    /// Search a ply deeper after giving check, so a fixed depth does not stop just before a mate.
+    pub check_extension: bool,
+    /// Search a ply deeper after the only legal move, which costs nothing to search.
+    pub single_reply_extension: bool,
+    /// Search a ply deeper after recapturing on the square of the last capture, to see the exchange through.
+    pub recapture_extension: bool,
+    /// Search a ply deeper after pushing a pawn to the seventh rank, a step from promoting.
+    pub pawn_push_extension: bool,
+    /// Most plies of extension along one line, so forcing lines cannot make the search explode.
+    pub max_extension: u8,
+}
+impl Default for Selectivity {
+    fn default() -> Self {
+        Selectivity {
+            null_move: true,
+            null_reduction: 2,
+            late_move_reductions: true,
+            full_depth_moves: 3,
+            reduction_limit: 3,
+            reduction: 1,
+            principal_variation_search: true,
+            aspiration_window: Some(Score::cp(25)),
+            check_extension: true,
+            single_reply_extension: true,
+            recapture_extension: true,
+            pawn_push_extension: true,
+            max_extension: 8,
+        }
+    }
+}
+impl Selectivity {
+    /// Plain alpha-beta to a fixed depth, pruning nothing but what it proves, with the full window everywhere.
+    pub fn none() -> Self {
+        Selectivity {
+            null_move: false,
+            late_move_reductions: false,
+            principal_variation_search: false,
+            aspiration_window: None,
+            check_extension: false,
+            single_reply_extension: false,
+            recapture_extension: false,
+            pawn_push_extension: false,
+            ..Selectivity::default()
+        }
+    }
+}
+
+/// How often the search had to search a move or the root again, for tuning window sizes and reductions.
+#[derive(Copy, Clone, Default, PartialEq, Eq, Debug)]
+pub struct SearchStats {
+    /// Moves searched with a null window after the first at a node.
+    pub null_windows: u64,
+    /// Null-window searches that beat alpha and were searched again with the full window.
+    pub null_window_re_searches: u64,
+    /// Late moves searched with a reduction.
+    pub reductions: u64,
+    /// Reduced searches that beat alpha and were searched again at full depth.
+    pub reduction_re_searches: u64,
+    /// Root searches in an aspiration window.
+    pub aspiration_searches: u64,
+    /// Root searches that scored above the window, and below it.
+    pub fail_highs: u64,
+    pub fail_lows: u64,
+}
+impl AddAssign for SearchStats {
+    fn add_assign(&mut self, rhs: SearchStats) {
+        self.null_windows += rhs.null_windows;
+        self.null_window_re_searches += rhs.null_window_re_searches;
+        self.reductions += rhs.reductions;
+        self.reduction_re_searches += rhs.reduction_re_searches;
+        self.aspiration_searches += rhs.aspiration_searches;
+        self.fail_highs += rhs.fail_highs;
+        self.fail_lows += rhs.fail_lows;
+    }
+}
+impl fmt::Display for SearchStats {
+    /// The re-search rates, such as "null window 3.1% of 9120, reduction 6.0% of 4410, aspiration 8.0% high 4.0% low
+    /// of 25".
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let percent = |count: u64, of: u64| 100.0 * count as f64 / of.max(1) as f64;
+        write!(
+            f,
+            "null window {:.1}% of {}, reduction {:.1}% of {}, aspiration {:.1}% high {:.1}% low of {}",
+            percent(self.null_window_re_searches, self.null_windows),
+            self.null_windows,
+            percent(self.reduction_re_searches, self.reductions),
+            self.reductions,
+            percent(self.fail_highs, self.aspiration_searches),
+            percent(self.fail_lows, self.aspiration_searches),
+            self.aspiration_searches
+        )
+    }
+}
+
+/// Alpha-beta search state for one thread.  Threads only share the transposition table, the stop flags and a node
+/// count for reporting.
+struct Searcher<'a> {
+    evaluator: &'a dyn Evaluator,
+    tt: &'a TranspositionTable,
+    /// Set when a thread finishes the search.
+    stop: &'a AtomicBool,
+    /// Set from outside the agent to end the search early.
+    halt: &'a AtomicBool,
+    total: &'a AtomicU64,
+    /// The game and the line being searched.
+    history: History,
+    /// Score of a draw for the side to move at the root.
+    draw_score: Score,
+    nodes: u64,
+    /// Nodes already added to `total`.
+    flushed: u64,
+    best: Option<ChessMove>,
+    /// Root moves left out, having been reported as better lines already.
+    excluded: Vec<ChessMove>,
+    selectivity: Selectivity,
+    stats: SearchStats,
+    /// Plies of extension along the line being searched.
+    extended: u8,
+    /// The square each move of the line captured on, if it captured.
+    captures: Vec<Option<Square>>,
+}
+impl<'a> Searcher<'a> {
+    fn new(
+        evaluator: &'a dyn Evaluator,
+        tt: &'a TranspositionTable,
+        stop: &'a AtomicBool,
+        halt: &'a AtomicBool,
+        total: &'a AtomicU64,
+        history: History,
+        draw_score: Score,
+    ) -> Self {
+        Searcher {
+            evaluator,
+            tt,
+            stop,
+            halt,
+            total,
+            history,
+            draw_score,
+            nodes: 0,
+            flushed: 0,
+            best: None,
+            excluded: Vec::new(),
+            selectivity: Selectivity::default(),
+            stats: SearchStats::default(),
+            extended: 0,
+            captures: Vec::new(),
+        }
+    }
+
+    fn stopped(&self) -> bool {
+        self.stop.load(Ordering::Relaxed) || self.halt.load(Ordering::Relaxed)
+    }
+
+    /// Add the nodes searched since the last call to the count shared by all threads.
+    fn flush_nodes(&mut self) {
+        self.total
+            .fetch_add(self.nodes - self.flushed, Ordering::Relaxed);
+        self.flushed = self.nodes;
+    }
+
+    /// Search the root to `depth`, within the aspiration window around the last iteration's score when there is one.
+    /// A score outside the window is only a bound, so that side of the window widens and the search runs again.
+    fn aspirate(&mut self, board: &Board, depth: u8, last: Option<Score>) -> Score {
+        let (Some(guess), Some(window)) = (
+            last.filter(|s| !s.is_mate()),
+            self.selectivity.aspiration_window,
+        ) else {
+            return self.negamax(board, depth, 0, -Score::INFINITY, Score::INFINITY, true);
+        };
+        let widen = |w: Score| {
+            if w >= Score::cp(MAX_WINDOW) {
+                Score::INFINITY
+            } else {
+                w + w
+            }
+        };
+        let (mut below, mut above) = (window, window);
+        loop {
+            let (alpha, beta) = (guess - below, guess + above);
+            self.stats.aspiration_searches += 1;
+            let score = self.negamax(board, depth, 0, alpha, beta, true);
+            if self.stopped() {
+                return score;
+            } else if score <= alpha && alpha > -Score::INFINITY {
+                self.stats.fail_lows += 1;
+                below = widen(below);
+            } else if score >= beta && beta < Score::INFINITY {
+                self.stats.fail_highs += 1;
+                above = widen(above);
+            } else {
+                return score;
+            }
+        }
+    }
+
+    /// Negamax alpha-beta search, scoring for the side to move with mates counted from the root.  Stopped searches
+    /// return zero, which callers must discard.  At the root (`ply` 0) the best move is kept in `best`.  `null_ok` is
+    /// false right after a pass, so a side never passes twice in a row.
+    fn negamax(
+        &mut self,
+        board: &Board,
+        depth: u8,
+        ply: u8,
+        mut alpha: Score,
+        beta: Score,
+        null_ok: bool,
+    ) -> Score {
+        self.nodes += 1;
+        if self.nodes.is_multiple_of(STOP_CHECK) {
+            self.flush_nodes();
+            if self.stopped() {
+                return Score::ZERO;
+            }
+        }
+        if ply > 0 && self.history.is_draw() {
+            return if ply.is_multiple_of(2) {
+                self.draw_score
+            } else {
+                -self.draw_score
+            };
+        }
+
+        // Use a deep enough stored result, and otherwise try its move first
+        let hash = board.get_hash();
+        let stored = self.tt.probe(hash);
+        if let Some(e) = stored.filter(|e| ply > 0 && e.depth >= depth) {
+            let score = e.score.to_root(ply);
+            match e.bound {
+                Bound::Exact => return score,
+                Bound::Lower if score >= beta => return score,
+                Bound::Upper if score <= alpha => return score,
+                _ => {}
+            }
+        }
+
+        let movegen = MoveGen::new_legal(board);
+        if movegen.len() == 0 {
+            return if *board.checkers() == chess::EMPTY {
+                Score::ZERO
+            } else {
+                Score::mated_in(ply)
+            };
+        }
+        if depth == 0 {
+            return self.quiesce(board, alpha, beta);
+        }
+
+        let in_check = *board.checkers() != chess::EMPTY;
+        let selectivity = self.selectivity;
+        if selectivity.null_move
+            && null_ok
+            && ply > 0
+            && !in_check
+            && depth > selectivity.null_reduction
+            && !beta.is_mate()
+            && has_pieces(board)
+            && self
+                .evaluator
+                .score_board(board, board.side_to_move() == Color::White)
+                >= beta
+        {
+            if let Some(passed) = board.null_move() {
+                self.history.push_null(&passed);
+                self.captures.push(None);
+                let reduced = depth - 1 - selectivity.null_reduction;
+                let score = -self.negamax(&passed, reduced, ply + 1, -beta, (-beta).above(), false);
+                self.captures.pop();
+                self.history.pop();
+                if self.stopped() {
+                    return Score::ZERO;
+                }
+                // Not the score itself, since a mate found after passing is not real
+                if score >= beta {
+                    return beta;
+                }
+            }
+        }
+
+        let alpha_start = alpha;
+        let mut best_score = -Score::INFINITY;
+        let mut best_move = None;
+        let single_reply = movegen.len() == 1;
+        for (i, m) in order(board, movegen, stored.and_then(|e| e.the_move))
+            .into_iter()
+            .enumerate()
+        {
+            if ply == 0 && self.excluded.contains(&m) {
+                continue;
+            }
+            let new_board = board.make_move_new(m);
+            let extension = self.extension(
+                board,
+                m,
+                &new_board,
+                single_reply,
+                depth.saturating_add(ply),
+            );
+            let child = depth - 1 + extension;
+            self.history.push(board, m, &new_board);
+            self.captures
+                .push(board.piece_on(m.get_dest()).map(|_| m.get_dest()));
+            self.extended += extension;
+            // A late quiet move gets a shallower null-window search first, and after the first move the rest get a
+            // null window at full depth, so only those that beat alpha get the full search
+            let null_window = selectivity.principal_variation_search && best_move.is_some();
+            let reduce = selectivity.late_move_reductions
+                && extension == 0
+                && ply > 0
+                && !in_check
+                && i >= selectivity.full_depth_moves
+                && depth >= selectivity.reduction_limit
+                && is_quiet(board, m)
+                && *new_board.checkers() == chess::EMPTY;
+            let mut score = Score::INFINITY;
+            if reduce {
+                let reduced = child.saturating_sub(selectivity.reduction);
+                score = -self.negamax(&new_board, reduced, ply + 1, -alpha.above(), -alpha, true);
+                self.stats.reductions += 1;
+                self.stats.reduction_re_searches += u64::from(score > alpha);
+            }
+            if score > alpha && null_window {
+                score = -self.negamax(&new_board, child, ply + 1, -alpha.above(), -alpha, true);
+                self.stats.null_windows += 1;
+                self.stats.null_window_re_searches += u64::from(score > alpha && score < beta);
+            }
+            // A null window that fails high at full depth has already shown the cutoff
+            if score > alpha && (score < beta || !null_window) {
+                score = -self.negamax(&new_board, child, ply + 1, -beta, -alpha, true);
+            }
+            self.extended -= extension;
+            self.captures.pop();
+            self.history.pop();
+            if self.stopped() {
+                return Score::ZERO;
+            }
+            if score > best_score {
+                best_score = score;
+                best_move = Some(m);
+            }
+            alpha = alpha.max(score);
+            if alpha >= beta {
+                break;
+            }
+        }
+
+        // A root searched without some moves is not the whole story, so leave the table to the full search
+        if ply == 0 && !self.excluded.is_empty() {
+            self.best = best_move;
+            return best_score;
+        }
+
+        let bound = if best_score >= beta {
+            Bound::Lower
+        } else if best_score > alpha_start {
+            Bound::Exact
+        } else {
+            Bound::Upper
+        };
+        self.tt.store(
+            hash,
+            Entry {
+                the_move: best_move,
+                score: best_score.to_node(ply),
+                depth,
+                bound,
+            },
+        );
+        if ply == 0 {
+            self.best = best_move;
+        }
+        best_score
+    }
+
+    /// One ply of extension for a move that starts a forcing line, while the line has extension left and the search
+    /// stays within the plies a mate score can count, `reach` being how far the line would go without it.
+    fn extension(
+        &self,
+        board: &Board,
+        m: ChessMove,
+        after: &Board,
+        single_reply: bool,
+        reach: u8,
+    ) -> u8 {
+        let s = self.selectivity;
+        if self.extended >= s.max_extension || i32::from(reach) >= MAX_PLY - 1 {
+            return 0;
+        }
+        let seventh = match board.side_to_move() {
+            Color::White => Rank::Seventh,
+            Color::Black => Rank::Second,
+        };
+        let forcing = (s.check_extension && *after.checkers() != chess::EMPTY)
+            || (s.single_reply_extension && single_reply)
+            || (s.recapture_extension && self.captures.last() == Some(&Some(m.get_dest())))
+            || (s.pawn_push_extension
+                && board.piece_on(m.get_source()) == Some(Piece::Pawn)
+                && m.get_dest().get_rank() == seventh);
+        u8::from(forcing)
+    }
+
+    /// Search captures only, so the score does not depend on stopping in the middle of an exchange.  Captures that
+    /// lose material in the exchange are left out, as standing pat does better.
+    fn quiesce(&mut self, board: &Board, mut alpha: Score, beta: Score) -> Score {
+        self.nodes += 1;
+        let stand_pat = self
+            .evaluator
+            .score_board(board, board.side_to_move() == Color::White);
+        if stand_pat >= beta {
+            return stand_pat;
+        }
+        alpha = alpha.max(stand_pat);
+
+        let mut movegen = MoveGen::new_legal(board);
+        movegen.set_iterator_mask(*board.color_combined(!board.side_to_move()));
+        for m in order(board, movegen, None) {
+            if see(board, m) < Score::ZERO {
+                continue;
+            }
+            let score = -self.quiesce(&board.make_move_new(m), -beta, -alpha);
+            if score >= beta {
+                return score;
+            }
+            alpha = alpha.max(score);
+        }
+        alpha
+    }
+}
+
+/// Whether the side to move has anything besides pawns and its king.
+fn has_pieces(board: &Board) -> bool {
+    let pawns_and_kings = board.pieces(Piece::Pawn) | board.pieces(Piece::King);
+    (board.color_combined(board.side_to_move()) & !pawns_and_kings) != chess::EMPTY
+}
+
+/// Neither a capture nor a promotion.
+fn is_quiet(board: &Board, m: ChessMove) -> bool {
+    board.piece_on(m.get_dest()).is_none() && m.get_promotion().is_none()
+}
+
+/// Order moves for alpha-beta: the stored best move, then captures that win or break even in the exchange, most
+/// first, then quiet moves and last captures that lose material.
+fn order(board: &Board, movegen: MoveGen, first: Option<ChessMove>) -> Vec<ChessMove> {
+    let mut moves: Vec<ChessMove> = movegen.collect();
+    moves.sort_by_cached_key(|m| match board.piece_on(m.get_dest()) {
+        _ if Some(*m) == first => (0, Score::ZERO),
+        Some(_) => match see(board, *m) {
+            gain if gain >= Score::ZERO => (1, -gain),
+            loss => (3, -loss),
+        },
+        None => (2, Score::ZERO),
+    });
+    moves
+}
+
+/// The best move followed by the stored best moves of the positions after it, up to `depth` moves.
+fn principal_variation(
+    tt: &TranspositionTable,
+    board: &Board,
+    best: Option<ChessMove>,
+    depth: u8,
+) -> Vec<ChessMove> {
+    let mut pv: Vec<ChessMove> = best.into_iter().collect();
+    let Some(m) = best else {
+        return pv;
+    };
+    let mut board = board.make_move_new(m);
+    while pv.len() < usize::from(depth) {
+        match tt.probe(board.get_hash()).and_then(|e| e.the_move) {
+            Some(m) if board.legal(m) => {
+                pv.push(m);
+                board = board.make_move_new(m);
+            }
+            _ => break,
+        }
+    }
+    pv
+}
+
+/// The deepest iteration any thread has completed, and who to tell about the next.
+struct Report<'a> {
+    info: Option<SearchInfo>,
+    callback: Option<&'a mut InfoCallback>,
+}
+
+/// A chess agent running an alpha-beta search with iterative deepening on several threads (Lazy SMP).
+/// Every thread searches the whole tree from the root, at slightly varied depths, and they share their results
+/// through a transposition table.
+pub struct SmpAgent {
+    evaluator: Box<dyn Evaluator>,
+    tt: TranspositionTable,
+    depth: u8,
+    threads: usize,
+    thresholds: Thresholds,
+    contempt: Score,
+    selectivity: Selectivity,
+    last_score: Option<Score>,
+    nodes: u64,
+    stats: SearchStats,
+    info: Option<SearchInfo>,
+    callback: Option<InfoCallback>,
+    halt: Arc<AtomicBool>,
+    /// Whether the stop flag cut the last search short of its depth.
+    stopped: bool,
+    start: Option<Start>,
+}
+impl Agent for SmpAgent {
+    fn make_move(&mut self, game: &chess::Game) -> Option<ChessMove> {
+        self.search(game).map(|(m, _)| m)
+    }
+
+    fn take_turn(&mut self, game: &chess::Game) -> Turn {
+        let best = self.search(game);
+        self.thresholds.turn(game, best)
+    }
+
+    fn accept_draw(&mut self, _game: &chess::Game) -> bool {
+        self.thresholds.accept(self.last_score)
+    }
+
+    fn name(&self) -> String {
+        if self.threads == 1 {
+            format!("SmpAgent({})", self.depth)
+        } else {
+            format!("SmpAgent({}, {} threads)", self.depth, self.threads)
+        }
+    }
+
+    fn set_info_callback(&mut self, callback: InfoCallback) {
+        self.callback = Some(callback);
+    }
+
+    fn last_info(&self) -> Option<SearchInfo> {
+        self.info.clone()
+    }
+
+    /// After the usual search, each further line is the best root move left when the better ones are excluded,
+    /// searched to the same depth on one thread using what the table already holds.
+    fn analyze(&mut self, game: &chess::Game, lines: usize) -> Vec<SearchInfo> {
+        let start = Instant::now();
+        if self.search(game).is_none() {
+            return Vec::new();
+        }
+        let mut infos = vec![self.info.clone().unwrap()];
+
+        let board = game.current_position();
+        let (stop, total, depth) = (
+            AtomicBool::new(false),
+            AtomicU64::new(self.nodes),
+            self.depth.max(1),
+        );
+        let history = History::from_game(game, self.start.as_ref());
+        let (evaluator, tt, halt) = (&*self.evaluator, &self.tt, &*self.halt);
+        let mut searcher =
+            Searcher::new(evaluator, tt, &stop, halt, &total, history, -self.contempt);
+        searcher.selectivity = self.selectivity;
+        while infos.len() < lines {
+            searcher.excluded = infos.iter().map(|i| i.pv[0]).collect();
+            searcher.best = None;
+            let score = searcher.negamax(&board, depth, 0, -Score::INFINITY, Score::INFINITY, true);
+            let Some(m) = searcher.best else {
+                break;
+            };
+            if searcher.stopped() {
+                self.stopped = true;
+                break;
+            }
+            searcher.flush_nodes();
+            let (nodes, pv) = (
+                total.load(Ordering::Relaxed),
+                principal_variation(&self.tt, &board, Some(m), depth),
+            );
+            infos.push(SearchInfo::new(
+                depth,
+                score,
+                nodes,
+                start.elapsed(),
+                self.tt.hashfull(),
+                pv,
+            ));
+        }
+        self.stats += searcher.stats;
+        self.nodes = total.into_inner();
+        infos
+    }
+
+    fn set_stop_flag(&mut self, stop: Arc<AtomicBool>) {
+        self.halt = stop;
+    }
+
+    fn stopped(&self) -> bool {
+        self.stopped
+    }
+
+    fn set_depth(&mut self, depth: u8) {
+        self.depth = depth;
+    }
+
+    fn deepens(&self) -> bool {
+        true
+    }
+
+    fn set_start(&mut self, start: Start) {
+        self.start = Some(start);
+    }
+}
+impl SmpAgent {
+    /// Search to `depth` using every available core and a 16 MB transposition table.
+    pub fn new(depth: u8) -> Self {
+        SmpAgent {
+            evaluator: Box::new(Calc::new()),
+            tt: TranspositionTable::new(16),
+            depth,
+            threads: std::thread::available_parallelism().map_or(1, |n| n.get()),
+            thresholds: Thresholds::default(),
+            contempt: Score::ZERO,
+            selectivity: Selectivity::default(),
+            last_score: None,
+            nodes: 0,
+            stats: SearchStats::default(),
+            info: None,
+            callback: None,
+            halt: Arc::new(AtomicBool::new(false)),
+            stopped: false,
+            start: None,
+        }
+    }
+
+    /// Set the scores at which to resign and to offer, accept or claim draws.
+    pub fn set_thresholds(&mut self, thresholds: Thresholds) {
+        self.thresholds = thresholds;
+    }
+
+    /// How much the agent dislikes a draw by repetition or the fifty-move rule, scoring one as `-contempt`.
+    pub fn set_contempt(&mut self, contempt: Score) {
+        self.contempt = contempt;
+    }
+
+    /// Choose which lines the search prunes or reduces.
+    pub fn set_selectivity(&mut self, selectivity: Selectivity) {
+        self.selectivity = selectivity;
+    }
+
+    /// Score positions with another evaluator than the default `Calc`, to compare evaluators with the same search.
+    /// The table holds the old evaluator's scores, so it is cleared.
+    pub fn set_evaluator(&mut self, evaluator: Box<dyn Evaluator>) {
+        self.evaluator = evaluator;
+        self.tt.clear();
+    }
+
+    /// Evaluate with a `Calc` weighing its piece terms so, to measure what each adds by playing with and without it.
+    pub fn set_weights(&mut self, weights: Weights) {
+        let mut calc = Calc::new();
+        calc.set_weights(weights);
+        self.set_evaluator(Box::new(calc));
+    }
+
+    /// Search with this many threads.
+    pub fn set_threads(&mut self, threads: usize) {
+        self.threads = threads.max(1);
+    }
+
+    /// Replace the transposition table with an empty one of about `megabytes`.
+    pub fn set_hash_size(&mut self, megabytes: usize) {
+        self.tt = TranspositionTable::new(megabytes);
+    }
+
+    /// Nodes searched by all threads for the last move.
+    pub fn nodes(&self) -> u64 {
+        self.nodes
+    }
+
+    /// How often the search had to search again, over every search since the agent was made.
+    pub fn stats(&self) -> SearchStats {
+        self.stats
+    }
+
+    /// Search for the best move, returning it with its score.
+    fn search(&mut self, game: &chess::Game) -> Option<(ChessMove, Score)> {
+        let board = game.current_position();
+        let (stop, total, start) = (AtomicBool::new(false), AtomicU64::new(0), Instant::now());
+        let (evaluator, tt, depth, threads, halt) = (
+            &*self.evaluator,
+            &self.tt,
+            self.depth.max(1),
+            self.threads,
+            &*self.halt,
+        );
+        let selectivity = self.selectivity;
+        let (history, draw_score) = (
+            &History::from_game(game, self.start.as_ref()),
+            -self.contempt,
+        );
+
+        let report = Mutex::new(Report {
+            info: None,
+            callback: self.callback.as_mut(),
+        });
+
+        let mut stats = SearchStats::default();
+        let nodes = std::thread::scope(|s| {
+            // Every thread deepens to the target, half the helpers starting a ply deeper so the threads spread over
+            // different depths.  Each iteration deeper than any before is reported, and the first to finish the
+            // target depth decides the move and stops the others.
+            let threads: Vec<_> = (0..threads)
+                .map(|id| {
+                    let (stop, total, report) = (&stop, &total, &report);
+                    s.spawn(move || {
+                        let mut searcher = Searcher::new(
+                            evaluator,
+                            tt,
+                            stop,
+                            halt,
+                            total,
+                            history.clone(),
+                            draw_score,
+                        );
+                        searcher.selectivity = selectivity;
+                        let mut last = None;
+                        for d in (1 + (id % 2) as u8).min(depth)..=depth {
+                            let score = searcher.aspirate(&board, d, last);
+                            if searcher.stopped() {
+                                break;
+                            }
+                            last = Some(score);
+                            searcher.flush_nodes();
+                            let mut report = report.lock().unwrap();
+                            if report.info.as_ref().is_none_or(|i| d > i.depth) {
+                                let pv = principal_variation(tt, &board, searcher.best, d);
+                                let nodes = total.load(Ordering::Relaxed);
+                                let info = SearchInfo::new(
+                                    d,
+                                    score,
+                                    nodes,
+                                    start.elapsed(),
+                                    tt.hashfull(),
+                                    pv,
+                                );
+                                if let Some(callback) = report.callback.as_mut() {
+                                    callback(&info);
+                                }
+                                report.info = Some(info);
+                                if d == depth {
+                                    stop.store(true, Ordering::Relaxed);
+                                }
+                            }
+                        }
+                        (searcher.nodes, searcher.stats)
+                    })
+                })
+                .collect();
+            let mut nodes = 0;
+            for t in threads {
+                let (n, thread_stats) = t.join().unwrap();
+                nodes += n;
+                stats += thread_stats;
+            }
+            nodes
+        });
+        let info = report.into_inner().unwrap().info;
+        let best = info
+            .as_ref()
+            .and_then(|i| i.pv.first().map(|m| (*m, i.score)));
+
+        self.nodes = nodes;
+        self.stats += stats;
+        // Only a stop keeps every thread from finishing the target depth, when there is a move at all
+        self.stopped =
+            info.as_ref().map(|i| i.depth) != Some(depth) && self.halt.load(Ordering::Relaxed);
+        self.info = info;
+        self.last_score = best.map(|(_, s)| s);
+        best
+    }
+}
+
+//==============================================================================
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::eval::Material;
+    use chess::{Game, Square};
+    use std::str::FromStr;
+    use std::sync::Arc;
+
+    /// A middlegame position with many captures, checks and quiet moves.
+    const KIWIPETE: &str = "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1";
+
+    fn search(fen: &str, depth: u8, threads: usize) -> (ChessMove, Score) {
+        let mut agent = SmpAgent::new(depth);
+        agent.set_threads(threads);
+        agent.set_hash_size(1);
+        agent
+            .search(&Game::new_with_board(Board::from_str(fen).unwrap()))
+            .unwrap()
+    }
+
+    #[test]
+    fn make_two_moves() {
+        let mut game = Game::new();
+        let mut white = SmpAgent::new(3);
+        let mut black = SmpAgent::new(2);
+        white.set_threads(1);
+        black.set_threads(4);
+        assert_eq!(white.name(), "SmpAgent(3)");
+        assert_eq!(black.name(), "SmpAgent(2, 4 threads)");
+
+        assert!(game.make_move(white.make_move(&game).unwrap()));
+        assert!(game.make_move(black.make_move(&game).unwrap()));
+        assert!(white.nodes() > 0);
+        assert!(!white.stopped());
+
+        // A set flag stops the search short of its depth
+        white.set_stop_flag(Arc::new(AtomicBool::new(true)));
+        white.set_depth(6);
+        white.make_move(&game);
+        assert!(white.stopped());
+    }
+
+    #[test]
+    fn mate() {
+        // Mate in one, and mate in two with a rook sacrifice, on one thread and several
+        for threads in [1, 4] {
+            let back_rank = "6k1/5ppp/8/8/8/8/5PPP/4R1K1 w - - 0 1";
+            let (m, score) = search(back_rank, 2, threads);
+            assert_eq!(
+                (m, score),
+                (
+                    ChessMove::new(Square::E1, Square::E8, None),
+                    Score::mate_in(1)
+                )
+            );
+            assert_eq!(score.to_string(), "mate 1");
+
+            // Searching deeper finds the same mate at the same distance
+            let sacrifice = "2r3k1/5ppp/8/8/8/8/4RPPP/4R1K1 w - - 0 1";
+            for depth in [3, 5] {
+                let best = (
+                    ChessMove::new(Square::E2, Square::E8, None),
+                    Score::mate_in(3),
+                );
+                assert_eq!(search(sacrifice, depth, threads), best);
+            }
+        }
+    }
+
+    #[test]
+    fn info() {
+        for threads in [1, 3] {
+            let depths = Arc::new(Mutex::new(Vec::new()));
+            let mut agent = SmpAgent::new(4);
+            agent.set_threads(threads);
+            // A small table, so the hash use shows in its sample
+            agent.set_hash_size(1);
+            let seen = depths.clone();
+            agent.set_info_callback(Box::new(move |info| seen.lock().unwrap().push(info.depth)));
+            let m = agent.make_move(&Game::new()).unwrap();
+
+            // Each deeper iteration is reported once, on one thread starting from the first
+            let depths = depths.lock().unwrap();
+            assert!(depths.windows(2).all(|w| w[0] < w[1]));
+            assert_eq!(depths.last(), Some(&4));
+            if threads == 1 {
+                assert_eq!(*depths, [1, 2, 3, 4]);
+            }
+
+            let info = agent.last_info().unwrap();
+            assert_eq!((info.depth, info.pv[0]), (4, m));
+            assert!(info
+                .pv
+                .iter()
+                .all(|m| ChessMove::from_str(&m.to_string()).is_ok()));
+            assert!(info.nodes > 0 && info.nodes <= agent.nodes());
+            assert!(info.hashfull > 0);
+        }
+    }
+
+    #[test]
+    fn analyze() {
+        // The mate first, then the other moves by their own scores
+        let game =
+            Game::new_with_board(Board::from_str("6k1/5ppp/8/8/8/8/5PPP/4R1K1 w - - 0 1").unwrap());
+        let mut agent = SmpAgent::new(3);
+        agent.set_threads(2);
+        let lines = agent.analyze(&game, 4);
+        assert_eq!(lines.len(), 4);
+        assert_eq!(
+            (lines[0].pv[0], lines[0].score),
+            (
+                ChessMove::new(Square::E1, Square::E8, None),
+                Score::mate_in(1)
+            )
+        );
+        assert!(lines.windows(2).all(|w| w[0].score >= w[1].score));
+        assert!(lines.iter().all(|l| !l.score.is_mate() || l == &lines[0]));
+        let mut moves: Vec<ChessMove> = lines.iter().map(|l| l.pv[0]).collect();
+        moves.dedup();
+        assert_eq!(moves.len(), 4);
+
+        // A plain search afterwards agrees with the first line
+        assert_eq!(agent.search(&game), Some((lines[0].pv[0], lines[0].score)));
+    }
+
+    #[test]
+    fn contempt() {
+        // Black can repeat the starting position by moving the knight back
+        let mut game = Game::new();
+        for m in ["g1f3", "g8f6", "f3g1"] {
+            assert!(game.make_move(ChessMove::from_str(m).unwrap()));
+        }
+        let back = ChessMove::from_str("f6g8").unwrap();
+
+        // Liking draws, black repeats, and disliking them it plays on
+        let mut agent = SmpAgent::new(2);
+        agent.set_threads(1);
+        agent.set_contempt(Score::cp(-500));
+        assert_eq!(agent.search(&game), Some((back, Score::cp(500))));
+        // A fresh table, since stored scores include the old draw score
+        agent.set_hash_size(1);
+        agent.set_contempt(Score::cp(500));
+        assert_ne!(agent.search(&game).unwrap().0, back);
+    }
+
+    #[test]
+    fn selectivity() {
+        // Each kind of selectivity searches fewer nodes on its own, and more so together, while still finding mates
+        let game = Game::new_with_board(Board::from_str(KIWIPETE).unwrap());
+        let nodes = |selectivity: Selectivity| {
+            let mut agent = SmpAgent::new(4);
+            agent.set_threads(1);
+            agent.set_selectivity(selectivity);
+            agent.make_move(&game).unwrap();
+            agent.nodes()
+        };
+        let none = Selectivity::none();
+        let full = nodes(none);
+        let null_move = nodes(Selectivity {
+            null_move: true,
+            ..none
+        });
+        let late_moves = nodes(Selectivity {
+            late_move_reductions: true,
+            ..none
+        });
+        let both = nodes(Selectivity {
+            null_move: true,
+            late_move_reductions: true,
+            ..none
+        });
+        assert!(
+            null_move < full && late_moves < full,
+            "{full} {null_move} {late_moves}"
+        );
+        assert!(
+            both < null_move.min(late_moves),
+            "{null_move} {late_moves} {both}"
+        );
+    }
+
+    #[test]
+    fn evaluators() {
+        // The evaluation's piece terms reach the search: with none, the opening scores as the tables do
+        let game = Game::new();
+        let score = |weights: Weights| {
+            let mut agent = SmpAgent::new(1);
+            agent.set_weights(weights);
+            agent.make_move(&game).unwrap();
+            agent.last_info().unwrap().score
+        };
+        let tables = Calc::new();
+        let best = MoveGen::new_legal(&game.current_position())
+            .map(|m| tables.score_board(&game.current_position().make_move_new(m), true))
+            .max();
+        assert_eq!(Some(score(Weights::none())), best);
+        assert_ne!(score(Weights::none()), score(Weights::default()));
+
+        // Any evaluator can replace it
+        let mut agent = SmpAgent::new(3);
+        agent.set_evaluator(Box::new(Material));
+        agent.make_move(&game).unwrap();
+        assert_eq!(agent.last_info().unwrap().score, Score::ZERO);
+    }
+
+    #[test]
+    fn windows() {
+        // Narrow windows find the same mate as full ones
+        let sacrifice = Game::new_with_board(
+            Board::from_str("2r3k1/5ppp/8/8/8/8/4RPPP/4R1K1 w - - 0 1").unwrap(),
+        );
+        let none = Selectivity::none();
+        let pvs = Selectivity {
+            principal_variation_search: true,
+            ..none
+        };
+        let aspiration = Selectivity {
+            aspiration_window: Some(Score::cp(10)),
+            ..none
+        };
+        let both = Selectivity {
+            aspiration_window: Some(Score::cp(10)),
+            ..pvs
+        };
+        for selectivity in [none, pvs, aspiration, both] {
+            let mut agent = SmpAgent::new(5);
+            agent.set_threads(1);
+            agent.set_selectivity(selectivity);
+            let best = (ChessMove::from_str("e2e8").unwrap(), Score::mate_in(3));
+            assert_eq!(agent.search(&sacrifice), Some(best));
+
+            // The jump to a mate fails high out of the window, which widens until it holds the mate
+            let stats = agent.stats();
+            assert_eq!(
+                stats.null_windows > 0,
+                selectivity.principal_variation_search
+            );
+            assert_eq!(
+                stats.aspiration_searches > 0,
+                selectivity.aspiration_window.is_some()
+            );
+            assert_eq!(
+                stats.fail_highs > 0,
+                selectivity.aspiration_window.is_some()
+            );
+            assert!(stats.null_window_re_searches <= stats.null_windows);
+            assert!(stats.fail_highs + stats.fail_lows <= stats.aspiration_searches);
+        }
+
+        let stats = SearchStats {
+            null_windows: 200,
+            null_window_re_searches: 5,
+            reductions: 0,
+            reduction_re_searches: 0,
+            aspiration_searches: 8,
+            fail_highs: 2,
+            fail_lows: 1,
+        };
+        let text =
+            "null window 2.5% of 200, reduction 0.0% of 0, aspiration 25.0% high 12.5% low of 8";
+        assert_eq!(stats.to_string(), text);
+    }
+
+    #[test]
+    fn extensions() {
+        // Mates in three, five plies deep, that a plain search to depth 3 stops short of
+        let mates = [
+            "2k5/r7/8/2K1Q3/7R/8/8/8 w - - 0 1",
+            "8/B5k1/8/8/2R5/3Q4/8/3K4 w - - 0 1",
+            "4K3/3Q4/8/1R2N3/1p6/8/1k6/8 w - - 0 1",
+        ];
+        let score = |fen: &str, depth: u8, selectivity: Selectivity| {
+            let mut agent = SmpAgent::new(depth);
+            agent.set_threads(1);
+            agent.set_selectivity(selectivity);
+            agent
+                .search(&Game::new_with_board(Board::from_str(fen).unwrap()))
+                .unwrap()
+                .1
+        };
+        for fen in mates {
+            assert_eq!(
+                score(fen, 5, Selectivity::none()),
+                Score::mate_in(5),
+                "{fen}"
+            );
+            assert!(!score(fen, 3, Selectivity::none()).is_mate(), "{fen}");
+            assert_eq!(
+                score(fen, 3, Selectivity::default()),
+                Score::mate_in(5),
+                "{fen}"
+            );
+
+            // Without any extension left for the line, the search is back to its fixed depth
+            let capped = Selectivity {
+                max_extension: 0,
+                ..Selectivity::default()
+            };
+            assert!(!score(fen, 3, capped).is_mate(), "{fen}");
+        }
+    }
+
+    #[test]
+    fn forcing_moves() {
+        let (calc, tt, stop, total) = (
+            Calc::new(),
+            TranspositionTable::new(1),
+            AtomicBool::new(false),
+            AtomicU64::new(0),
+        );
+        let none = Selectivity::none();
+        let kinds = [
+            // Checks, pushes to the seventh and recaptures on d5, each with only its own extension
+            (
+                "7k/8/8/8/8/8/8/R3K3 w - - 0 1",
+                "a1a8",
+                "a1a2",
+                Selectivity {
+                    check_extension: true,
+                    ..none
+                },
+            ),
+            (
+                "7k/8/3P4/8/8/8/8/4K3 w - - 0 1",
+                "d6d7",
+                "e1e2",
+                Selectivity {
+                    pawn_push_extension: true,
+                    ..none
+                },
+            ),
+            (
+                "7k/8/8/3p4/4P3/8/8/4K3 w - - 0 1",
+                "e4d5",
+                "e4e5",
+                Selectivity {
+                    recapture_extension: true,
+                    ..none
+                },
+            ),
+        ];
+        for (fen, forcing, quiet, selectivity) in kinds {
+            let board = Board::from_str(fen).unwrap();
+            let mut searcher = Searcher::new(
+                &calc,
+                &tt,
+                &stop,
+                &stop,
+                &total,
+                History::new(&board),
+                Score::ZERO,
+            );
+            searcher.selectivity = selectivity;
+            searcher.captures.push(Some(Square::D5));
+            let extension = |searcher: &Searcher, m: &str| {
+                let m = ChessMove::from_str(m).unwrap();
+                searcher.extension(&board, m, &board.make_move_new(m), false, 3)
+            };
+            assert_eq!(
+                (extension(&searcher, forcing), extension(&searcher, quiet)),
+                (1, 0),
+                "{fen}"
+            );
+
+            // Not once the line has used up its extension, nor with all of them off
+            searcher.extended = selectivity.max_extension;
+            assert_eq!(extension(&searcher, forcing), 0);
+            searcher.extended = 0;
+            searcher.selectivity = none;
+            assert_eq!(extension(&searcher, forcing), 0);
+        }
+
+        // The only legal move
+        let board = Board::from_str("7k/8/8/8/8/8/8/4K3 w - - 0 1").unwrap();
+        let mut searcher = Searcher::new(
+            &calc,
+            &tt,
+            &stop,
+            &stop,
+            &total,
+            History::new(&board),
+            Score::ZERO,
+        );
+        searcher.selectivity = Selectivity {
+            single_reply_extension: true,
+            ..none
+        };
+        let m = ChessMove::from_str("e1e2").unwrap();
+        assert_eq!(
+            searcher.extension(&board, m, &board.make_move_new(m), true, 3),
+            1
+        );
+    }
+
+    #[test]
+    fn move_order() {
+        // Taking the loose knight first, then the quiet moves, and the rook taking a guarded pawn last
+        let board = Board::from_str("4k3/8/2p5/3p3n/8/8/8/3RK2R w - - 0 1").unwrap();
+        let moves = order(&board, MoveGen::new_legal(&board), None);
+        assert_eq!(moves.first(), Some(&ChessMove::from_str("h1h5").unwrap()));
+        assert_eq!(moves.last(), Some(&ChessMove::from_str("d1d5").unwrap()));
+
+        // The stored move still goes first
+        let stored = ChessMove::from_str("e1f2").unwrap();
+        assert_eq!(
+            order(&board, MoveGen::new_legal(&board), Some(stored))[..2],
+            [stored, moves[0]]
+        );
+    }
+
+    #[test]
+    fn zugzwang() {
+        // Mates in three where the defender, with only pawns to spare, runs out of safe moves.  Passing would be
+        // the defence, so these are lost when null-move pruning is allowed with only pawns.
+        let mates = [
+            ("8/8/8/8/2p5/5R2/3K4/k7 w - - 0 1", "d2c2"),
+            ("4k3/8/5K2/8/8/8/2p2Q2/8 w - - 0 1", "f6e6"),
+            ("5k2/8/Q3K3/4p3/8/8/8/8 w - - 0 1", "a6a7"),
+        ];
+        for (fen, m) in mates {
+            let game = Game::new_with_board(Board::from_str(fen).unwrap());
+            for selectivity in [Selectivity::default(), Selectivity::none()] {
+                let mut agent = SmpAgent::new(6);
+                agent.set_threads(1);
+                agent.set_hash_size(1);
+                agent.set_selectivity(selectivity);
+                assert_eq!(
+                    agent.search(&game),
+                    Some((ChessMove::from_str(m).unwrap(), Score::mate_in(5))),
+                    "{fen}"
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn no_moves() {
+        let mut agent = SmpAgent::new(2);
+        let game = Game::new_with_board(Board::from_str("7k/6Q1/7K/8/8/8/8/8 b - - 0 1").unwrap());
+        assert_eq!(agent.search(&game), None);
+    }
+}
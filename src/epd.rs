@@ -0,0 +1,220 @@
+use crate::limit::{choose_move, Limit};
+use crate::Agent;
+use chess::{Board, ChessMove, Game};
+use std::str::FromStr;
+use std::time::{Duration, Instant};
+
+/// A test position from an EPD file, with the best (bm) and avoid (am) moves.
+pub struct EpdPosition {
+    pub id: String,
+    pub board: Board,
+    pub best_moves: Vec<ChessMove>,
+    pub avoid_moves: Vec<ChessMove>,
+}
+impl EpdPosition {
+    /// Parse a single EPD record, e.g. `<board> <side> <castle> <ep> bm Qg6; id "WAC.001";`
+    pub fn parse(line: &str) -> Result<Self, String> {
+        let fields: Vec<&str> = line.split_whitespace().collect();
+        if fields.len() < 4 {
+            return Err(format!("Not enough fields in EPD: {line}"));
+        }
+        let board = Board::from_str(&fields[0..4].join(" ")).map_err(|e| format!("{e}: {line}"))?;
+
+        let mut position = EpdPosition {
+            id: String::new(),
+            board,
+            best_moves: Vec::new(),
+            avoid_moves: Vec::new(),
+        };
+
+        // The opcodes follow the board, each terminated by a semicolon
+        for op in fields[4..].join(" ").split(';') {
+            let mut tokens = op.split_whitespace();
+            match tokens.next() {
+                Some("bm") => position.best_moves = parse_moves(&board, tokens)?,
+                Some("am") => position.avoid_moves = parse_moves(&board, tokens)?,
+                Some("id") => {
+                    position.id = tokens
+                        .collect::<Vec<&str>>()
+                        .join(" ")
+                        .trim_matches('"')
+                        .to_string()
+                }
+                _ => {}
+            }
+        }
+
+        if position.best_moves.is_empty() && position.avoid_moves.is_empty() {
+            return Err(format!("No bm or am opcode in EPD: {line}"));
+        }
+        Ok(position)
+    }
+
+    /// A move solves the position if it is a best move (when given) and not an avoid move.
+    pub fn is_solved(&self, the_move: ChessMove) -> bool {
+        (self.best_moves.is_empty() || self.best_moves.contains(&the_move))
+            && !self.avoid_moves.contains(&the_move)
+    }
+}
+
+/// Convert SAN operands to moves, tolerating the check, annotation and promotion markers the `chess` crate rejects.
+fn parse_moves<'a>(
+    board: &Board,
+    tokens: impl Iterator<Item = &'a str>,
+) -> Result<Vec<ChessMove>, String> {
+    let mut moves = Vec::new();
+    for t in tokens {
+        let san: String = t
+            .chars()
+            .filter(|c| !matches!(c, '+' | '#' | '!' | '?' | '='))
+            .collect();
+        let m = ChessMove::from_san(board, &san).map_err(|_| format!("Invalid SAN move {t}"))?;
+        moves.push(m);
+    }
+    Ok(moves)
+}
+
+/// Load every position from an EPD file, skipping blank and comment lines.
+pub fn load(path: &str) -> Result<Vec<EpdPosition>, String> {
+    let text = std::fs::read_to_string(path).map_err(|e| format!("{path}: {e}"))?;
+    text.lines()
+        .map(str::trim)
+        .filter(|l| !l.is_empty() && !l.starts_with('#'))
+        .map(EpdPosition::parse)
+        .collect()
+}
+
+/// The outcome of a single position.
+pub struct PositionResult {
+    pub id: String,
+    pub chosen: Option<ChessMove>,
+    pub solved: bool,
+    pub depth: u8,
+    pub elapsed: Duration,
+}
+
+/// The outcome of a whole suite.
+pub struct SuiteResult {
+    pub name: String,
+    pub results: Vec<PositionResult>,
+}
+impl SuiteResult {
+    pub fn solved(&self) -> usize {
+        self.results.iter().filter(|r| r.solved).count()
+    }
+
+    pub fn total(&self) -> usize {
+        self.results.len()
+    }
+
+    /// Print one line per position followed by the solved/total summary.
+    pub fn print(&self) {
+        println!("{}", self.name);
+        let mut elapsed = Duration::ZERO;
+        for r in &self.results {
+            let chosen = r.chosen.map_or("none".to_string(), |m| m.to_string());
+            let mark = if r.solved { "ok" } else { "--" };
+            println!(
+                "  {mark} {:<12} {chosen:<6} depth {}, {:.2?}",
+                r.id, r.depth, r.elapsed
+            );
+            elapsed += r.elapsed;
+        }
+        println!(
+            "  solved {}/{}, total {:.2?}",
+            self.solved(),
+            self.total(),
+            elapsed
+        );
+    }
+}
+
+/// Run every position with a fresh agent from `make_agent`, which is given the depth to search.
+pub fn run_suite(
+    make_agent: &dyn Fn(u8) -> Box<dyn Agent>,
+    positions: &[EpdPosition],
+    limit: Limit,
+) -> SuiteResult {
+    let mut results = Vec::new();
+    let mut name = String::new();
+
+    for p in positions {
+        let start = Instant::now();
+        let choice = choose_move(make_agent, &Game::new_with_board(p.board), limit);
+        name = choice.name;
+
+        results.push(PositionResult {
+            id: p.id.clone(),
+            chosen: choice.the_move,
+            solved: choice.the_move.is_some_and(|m| p.is_solved(m)),
+            depth: choice.depth,
+            elapsed: start.elapsed(),
+        });
+    }
+
+    SuiteResult { name, results }
+}
+
+//==============================================================================
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::agent_depth::DepthAgent;
+    use crate::agent_simple::SimpleAgent;
+    use chess::Square;
+
+    // Back rank mate in one: Re8#
+    const MATE_IN_ONE: &str = r#"6k1/5ppp/8/8/8/8/5PPP/4R1K1 w - - bm Re8#; id "mate.001";"#;
+
+    #[test]
+    fn parse() {
+        let p = EpdPosition::parse(MATE_IN_ONE).unwrap();
+        assert_eq!(p.id, "mate.001");
+        assert_eq!(
+            p.best_moves,
+            vec![ChessMove::new(Square::E1, Square::E8, None)]
+        );
+        assert!(p.avoid_moves.is_empty());
+
+        let p = EpdPosition::parse("4k3/8/8/8/8/8/8/4K2R w K - am O-O; id \"castle\";").unwrap();
+        assert_eq!(
+            p.avoid_moves,
+            vec![ChessMove::new(Square::E1, Square::G1, None)]
+        );
+        assert!(p.is_solved(ChessMove::new(Square::E1, Square::F1, None)));
+        assert!(!p.is_solved(ChessMove::new(Square::E1, Square::G1, None)));
+
+        // Missing opcodes and illegal moves are errors
+        assert!(EpdPosition::parse("4k3/8/8/8/8/8/8/4K2R w K -").is_err());
+        assert!(EpdPosition::parse("4k3/8/8/8/8/8/8/4K2R w K - bm Qh5;").is_err());
+    }
+
+    #[test]
+    fn run_depth() {
+        let positions = vec![EpdPosition::parse(MATE_IN_ONE).unwrap()];
+        let result = run_suite(
+            &|_| Box::new(SimpleAgent::new()),
+            &positions,
+            Limit::Depth(1),
+        );
+        assert_eq!(result.name, "SimpleAgent");
+        assert_eq!(result.solved(), 1);
+        assert_eq!(result.total(), 1);
+        assert_eq!(
+            result.results[0].chosen,
+            Some(ChessMove::new(Square::E1, Square::E8, None))
+        );
+    }
+
+    #[test]
+    fn run_time() {
+        let positions = vec![EpdPosition::parse(MATE_IN_ONE).unwrap()];
+        let result = run_suite(
+            &|d| Box::new(DepthAgent::new(d)),
+            &positions,
+            Limit::Time(Duration::from_millis(50)),
+        );
+        assert_eq!(result.solved(), 1);
+        assert!(result.results[0].depth >= 1);
+    }
+}
@@ -0,0 +1,192 @@
+use crate::score::{Calc, Score};
+use crate::see::PIECE_VALUES;
+use chess::{Board, ChessMove, Color, Piece};
+
+/// What an evaluator keeps with each node beside its score, for the parts of its evaluation that the score alone
+/// cannot be updated from.  The searches hand it from each board to the boards after its moves, as they do the score,
+/// and evaluators that need none leave it at the default.
+#[derive(Copy, Clone, Default, PartialEq, Eq, Debug)]
+pub struct NodeState(pub i32);
+
+/// Scores positions for the agents, which search the same way whichever evaluator they are given, so evaluators can be
+/// compared by playing otherwise identical agents against each other.
+pub trait Evaluator: Send + Sync {
+    /// Score the board for White if `is_white`, otherwise for Black.
+    fn score_board(&self, board: &Board, is_white: bool) -> Score;
+
+    /// Score the board after the move, from `score`, the board's score before it.  The searches keep that score with
+    /// each node, so an evaluator can update it move by move instead of scoring every board in full, which it does by
+    /// default.
+    fn score_move(
+        &self,
+        board: &Board,
+        is_white: bool,
+        the_move: ChessMove,
+        score: Score,
+    ) -> Score {
+        let _ = score;
+        self.score_board(&board.make_move_new(the_move), is_white)
+    }
+
+    /// The state of a board the searches start from, such as their root.  By default there is none.
+    fn node_state(&self, _board: &Board, _is_white: bool) -> NodeState {
+        NodeState::default()
+    }
+
+    /// Score the board after the move from the board's score and state, returning the score and state after it.  By
+    /// default the state is passed on unchanged and the score comes from `score_move`.
+    fn score_move_with(
+        &self,
+        board: &Board,
+        is_white: bool,
+        the_move: ChessMove,
+        score: Score,
+        state: NodeState,
+    ) -> (Score, NodeState) {
+        (self.score_move(board, is_white, the_move, score), state)
+    }
+}
+
+/// The piece-square tables, pawn structure and piece terms, scoring the tables move by move and carrying the terms in
+/// the node state.
+impl Evaluator for Calc {
+    fn score_board(&self, board: &Board, is_white: bool) -> Score {
+        Calc::score_board(self, board, is_white)
+    }
+
+    fn score_move(
+        &self,
+        board: &Board,
+        is_white: bool,
+        the_move: ChessMove,
+        score: Score,
+    ) -> Score {
+        Calc::score_move(self, board, is_white, the_move, score)
+    }
+
+    fn node_state(&self, board: &Board, is_white: bool) -> NodeState {
+        Calc::node_state(self, board, is_white)
+    }
+
+    fn score_move_with(
+        &self,
+        board: &Board,
+        is_white: bool,
+        the_move: ChessMove,
+        score: Score,
+        state: NodeState,
+    ) -> (Score, NodeState) {
+        Calc::score_move_with(self, board, is_white, the_move, score, state)
+    }
+}
+
+/// Counts material alone, by the exchange values, updating it only when a move captures or promotes.
+#[derive(Copy, Clone, Default)]
+pub struct Material;
+impl Evaluator for Material {
+    fn score_board(&self, board: &Board, is_white: bool) -> Score {
+        let side = |color| -> i32 {
+            [
+                Piece::Pawn,
+                Piece::Knight,
+                Piece::Bishop,
+                Piece::Rook,
+                Piece::Queen,
+            ]
+            .into_iter()
+            .map(|p| {
+                PIECE_VALUES[p.to_index()]
+                    * (board.pieces(p) & board.color_combined(color)).popcnt() as i32
+            })
+            .sum()
+        };
+        let white = side(Color::White) - side(Color::Black);
+        Score::cp(if is_white { white } else { -white })
+    }
+
+    fn score_move(
+        &self,
+        board: &Board,
+        is_white: bool,
+        the_move: ChessMove,
+        score: Score,
+    ) -> Score {
+        let (from, to) = (the_move.get_source(), the_move.get_dest());
+        let mut gain = board.piece_on(to).map_or(0, |p| PIECE_VALUES[p.to_index()]);
+        if board.piece_on(from) == Some(Piece::Pawn)
+            && from.get_file() != to.get_file()
+            && gain == 0
+        {
+            // En passant
+            gain = PIECE_VALUES[Piece::Pawn.to_index()];
+        }
+        if let Some(promotion) = the_move.get_promotion() {
+            gain += PIECE_VALUES[promotion.to_index()] - PIECE_VALUES[Piece::Pawn.to_index()];
+        }
+        if (board.side_to_move() == Color::White) == is_white {
+            score + Score::cp(gain)
+        } else {
+            score - Score::cp(gain)
+        }
+    }
+}
+
+//==============================================================================
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::terms::Weights;
+    use chess::MoveGen;
+    use std::str::FromStr;
+
+    /// Every legal move scored from the board's score, and from its score and state, matches the board after it
+    /// scored in full, with that board's state.
+    fn incremental(evaluator: &dyn Evaluator, fen: &str) {
+        let board = Board::from_str(fen).unwrap();
+        for is_white in [true, false] {
+            let (score, state) = (
+                evaluator.score_board(&board, is_white),
+                evaluator.node_state(&board, is_white),
+            );
+            for m in MoveGen::new_legal(&board) {
+                let after = board.make_move_new(m);
+                let full = evaluator.score_board(&after, is_white);
+                assert_eq!(
+                    evaluator.score_move(&board, is_white, m, score),
+                    full,
+                    "{fen} {m}"
+                );
+                let with = evaluator.score_move_with(&board, is_white, m, score, state);
+                assert_eq!(
+                    with,
+                    (full, evaluator.node_state(&after, is_white)),
+                    "{fen} {m}"
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn material() {
+        assert_eq!(Material.score_board(&Board::default(), true), Score::ZERO);
+        let board = Board::from_str("4k3/8/8/8/8/8/8/R3K3 w - - 0 1").unwrap();
+        assert_eq!(Material.score_board(&board, true), Score::cp(500));
+        assert_eq!(Material.score_board(&board, false), Score::cp(-500));
+
+        // Captures, en passant and promotions, for both sides
+        incremental(&Material, "r3k3/1P6/8/3pP3/2n5/1B6/8/4K3 w - d6 0 1");
+        incremental(&Material, "4k3/8/8/8/3pP3/2N5/6p1/4K2R b - e3 0 1");
+    }
+
+    #[test]
+    fn calc() {
+        // The tables alone are scored move by move, and the full evaluation the same as board by board, with castling,
+        // en passant and promotions for both sides
+        let mut full = Calc::with_pawn_structure();
+        full.set_weights(Weights::default());
+        for evaluator in [Calc::new(), full] {
+            incremental(&evaluator, "r3k2r/1P6/8/3pP3/2n5/1B6/8/R3K2R w KQkq d6 0 1");
+            incremental(&evaluator, "r3k2r/8/8/8/3pP3/2N5/6p1/R3K2R b KQkq e3 0 1");
+        }
+    }
+}
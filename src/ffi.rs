@@ -0,0 +1,283 @@
+use crate::limit::{choose_move_with, Limit};
+use crate::{agent_by_name, Agent, Start, Thresholds};
+use chess::Game;
+use std::ffi::{c_char, CStr};
+use std::panic::{catch_unwind, AssertUnwindSafe};
+use std::time::Duration;
+
+/// The call succeeded.
+pub const CHESS_AI_OK: i32 = 0;
+/// A pointer was null, a string was not UTF-8, or the agent name is unknown.
+pub const CHESS_AI_INVALID_ARGUMENT: i32 = -1;
+/// The FEN could not be parsed.
+pub const CHESS_AI_INVALID_FEN: i32 = -2;
+/// The position has no legal move.
+pub const CHESS_AI_NO_MOVE: i32 = -3;
+/// The output buffer cannot hold the text and its terminating null.
+pub const CHESS_AI_BUFFER_TOO_SMALL: i32 = -4;
+/// The library failed inside; the agent may be left in a poor state and is best freed.
+pub const CHESS_AI_PANIC: i32 = -5;
+
+/// An agent created for a C caller, which only ever sees a pointer to it.
+pub struct ChessAgent {
+    depth: u8,
+    agent: Box<dyn Agent>,
+    time_limit: Option<Duration>,
+}
+
+/// Create an agent by name ("random", "simple", "depth", "thread", "smp" or "mcts") searching to `depth`.
+/// Returns null if the name is unknown or creating it fails.  Free it with `chess_ai_agent_free`.
+///
+/// # Safety
+/// `name` must be null or a valid null-terminated string.
+#[no_mangle]
+pub unsafe extern "C" fn chess_ai_agent_new(name: *const c_char, depth: u8) -> *mut ChessAgent {
+    let Some(name) = to_str(name) else {
+        return std::ptr::null_mut();
+    };
+    let agent = catch_unwind(|| agent_by_name(name, depth, Thresholds::default()));
+    match agent {
+        Ok(Some(agent)) => Box::into_raw(Box::new(ChessAgent {
+            depth,
+            agent,
+            time_limit: None,
+        })),
+        _ => std::ptr::null_mut(),
+    }
+}
+
+/// Destroy an agent from `chess_ai_agent_new`.  Null is ignored.
+///
+/// # Safety
+/// `agent` must be null or a pointer from `chess_ai_agent_new` that has not already been freed.
+#[no_mangle]
+pub unsafe extern "C" fn chess_ai_agent_free(agent: *mut ChessAgent) {
+    if !agent.is_null() {
+        let _ = catch_unwind(AssertUnwindSafe(|| drop(Box::from_raw(agent))));
+    }
+}
+
+/// Limit each move to `millis` milliseconds, deepening one ply at a time.  Zero goes back to the fixed depth.
+///
+/// # Safety
+/// `agent` must be null or a live pointer from `chess_ai_agent_new`.
+#[no_mangle]
+pub unsafe extern "C" fn chess_ai_agent_set_time_limit(agent: *mut ChessAgent, millis: u64) -> i32 {
+    let Some(agent) = agent.as_mut() else {
+        return CHESS_AI_INVALID_ARGUMENT;
+    };
+    guard(|| {
+        agent.time_limit = (millis > 0).then(|| Duration::from_millis(millis));
+        CHESS_AI_OK
+    })
+}
+
+/// Write the agent's best move for the FEN position into `out` as UCI text (e.g. "e2e4" or "e7e8q").  With a time
+/// limit the agent searches one ply deeper at a time, then goes back to its depth.
+///
+/// # Safety
+/// `agent` must be null or a live pointer from `chess_ai_agent_new`, `fen` must be null or a valid
+/// null-terminated string, and `out` must be null or point to at least `out_len` writable bytes.
+#[no_mangle]
+pub unsafe extern "C" fn chess_ai_agent_best_move(
+    agent: *mut ChessAgent,
+    fen: *const c_char,
+    out: *mut c_char,
+    out_len: usize,
+) -> i32 {
+    let (Some(agent), Some(fen)) = (agent.as_mut(), to_str(fen)) else {
+        return CHESS_AI_INVALID_ARGUMENT;
+    };
+    guard(|| {
+        let Some(start) = Start::from_fen(fen) else {
+            return CHESS_AI_INVALID_FEN;
+        };
+
+        let game = Game::new_with_board(start.board);
+        agent.agent.set_start(start);
+        let the_move = match agent.time_limit {
+            Some(t) => {
+                let choice = choose_move_with(&mut *agent.agent, &game, Limit::Time(t));
+                agent.agent.set_depth(agent.depth);
+                choice.the_move
+            }
+            None => agent.agent.make_move(&game),
+        };
+
+        match the_move {
+            Some(m) => write_str(&m.to_string(), out, out_len),
+            None => CHESS_AI_NO_MOVE,
+        }
+    })
+}
+
+/// Write the agent's display name, such as "DepthAgent(3)", into `out`.
+///
+/// # Safety
+/// `agent` must be null or a live pointer from `chess_ai_agent_new`, and `out` must be null or point to at
+/// least `out_len` writable bytes.
+#[no_mangle]
+pub unsafe extern "C" fn chess_ai_agent_name(
+    agent: *const ChessAgent,
+    out: *mut c_char,
+    out_len: usize,
+) -> i32 {
+    match agent.as_ref() {
+        Some(agent) => guard(|| write_str(&agent.agent.name(), out, out_len)),
+        None => CHESS_AI_INVALID_ARGUMENT,
+    }
+}
+
+/// Run the body of a call, returning `CHESS_AI_PANIC` if it panics, since unwinding into C is undefined behaviour.
+fn guard(body: impl FnOnce() -> i32) -> i32 {
+    catch_unwind(AssertUnwindSafe(body)).unwrap_or(CHESS_AI_PANIC)
+}
+
+/// Borrow a C string as UTF-8.
+unsafe fn to_str<'a>(s: *const c_char) -> Option<&'a str> {
+    if s.is_null() {
+        None
+    } else {
+        CStr::from_ptr(s).to_str().ok()
+    }
+}
+
+/// Copy text into a C buffer with a terminating null.
+unsafe fn write_str(text: &str, out: *mut c_char, out_len: usize) -> i32 {
+    if out.is_null() {
+        return CHESS_AI_INVALID_ARGUMENT;
+    }
+    if text.len() >= out_len {
+        return CHESS_AI_BUFFER_TOO_SMALL;
+    }
+    std::ptr::copy_nonoverlapping(text.as_ptr().cast::<c_char>(), out, text.len());
+    *out.add(text.len()) = 0;
+    CHESS_AI_OK
+}
+
+//==============================================================================
+#[cfg(test)]
+mod test {
+    use super::*;
+    use chess::Board;
+    use std::ffi::CString;
+    use std::str::FromStr;
+
+    const START: &str = "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1";
+
+    /// Call `chess_ai_agent_best_move` and return the code and the text written.
+    fn best_move(agent: *mut ChessAgent, fen: &str, out_len: usize) -> (i32, String) {
+        let fen = CString::new(fen).unwrap();
+        let mut out = vec![0 as c_char; out_len.max(1)];
+        let code =
+            unsafe { chess_ai_agent_best_move(agent, fen.as_ptr(), out.as_mut_ptr(), out_len) };
+        let text = unsafe { CStr::from_ptr(out.as_ptr()) }
+            .to_str()
+            .unwrap()
+            .to_string();
+        (code, text)
+    }
+
+    #[test]
+    fn create_and_free() {
+        let name = CString::new("depth").unwrap();
+        let agent = unsafe { chess_ai_agent_new(name.as_ptr(), 2) };
+        assert!(!agent.is_null());
+
+        let mut out = [0 as c_char; 32];
+        assert_eq!(
+            unsafe { chess_ai_agent_name(agent, out.as_mut_ptr(), out.len()) },
+            CHESS_AI_OK
+        );
+        assert_eq!(
+            unsafe { CStr::from_ptr(out.as_ptr()) }.to_str().unwrap(),
+            "DepthAgent(2)"
+        );
+        unsafe { chess_ai_agent_free(agent) };
+
+        // Unknown names and null pointers are rejected, and freeing null is harmless
+        let name = CString::new("nobody").unwrap();
+        assert!(unsafe { chess_ai_agent_new(name.as_ptr(), 2) }.is_null());
+        assert!(unsafe { chess_ai_agent_new(std::ptr::null(), 2) }.is_null());
+        unsafe { chess_ai_agent_free(std::ptr::null_mut()) };
+    }
+
+    #[test]
+    fn best_moves() {
+        let name = CString::new("simple").unwrap();
+        let agent = unsafe { chess_ai_agent_new(name.as_ptr(), 1) };
+
+        let (code, text) = best_move(agent, START, 6);
+        assert_eq!(code, CHESS_AI_OK);
+        assert!(Board::default().legal(chess::ChessMove::from_str(&text).unwrap()));
+
+        // Mate in one, with a promotion to a queen
+        assert_eq!(
+            best_move(agent, "7k/P5R1/7K/8/8/8/8/8 w - - 0 1", 6),
+            (CHESS_AI_OK, "a7a8q".to_string())
+        );
+
+        assert_eq!(best_move(agent, "not a fen", 6).0, CHESS_AI_INVALID_FEN);
+        assert_eq!(
+            best_move(agent, "7k/6Q1/7K/8/8/8/8/8 b - - 0 1", 6).0,
+            CHESS_AI_NO_MOVE
+        );
+        assert_eq!(best_move(agent, START, 4).0, CHESS_AI_BUFFER_TOO_SMALL);
+        assert_eq!(
+            best_move(std::ptr::null_mut(), START, 6).0,
+            CHESS_AI_INVALID_ARGUMENT
+        );
+        unsafe { chess_ai_agent_free(agent) };
+    }
+
+    #[test]
+    fn time_limit() {
+        let name = CString::new("thread").unwrap();
+        let agent = unsafe { chess_ai_agent_new(name.as_ptr(), 1) };
+        assert_eq!(
+            unsafe { chess_ai_agent_set_time_limit(agent, 50) },
+            CHESS_AI_OK
+        );
+        let (code, text) = best_move(agent, START, 6);
+        assert_eq!(code, CHESS_AI_OK);
+        assert!(Board::default().legal(chess::ChessMove::from_str(&text).unwrap()));
+
+        assert_eq!(
+            unsafe { chess_ai_agent_set_time_limit(agent, 0) },
+            CHESS_AI_OK
+        );
+        assert_eq!(
+            unsafe { chess_ai_agent_set_time_limit(std::ptr::null_mut(), 0) },
+            CHESS_AI_INVALID_ARGUMENT
+        );
+
+        // The agent searched is the one created, back at its own depth
+        let mut out = [0 as c_char; 32];
+        assert_eq!(
+            unsafe { chess_ai_agent_name(agent, out.as_mut_ptr(), out.len()) },
+            CHESS_AI_OK
+        );
+        assert!(unsafe { CStr::from_ptr(out.as_ptr()) }
+            .to_str()
+            .unwrap()
+            .starts_with("ThreadAgent(1"));
+        unsafe { chess_ai_agent_free(agent) };
+    }
+
+    #[test]
+    fn header_is_current() {
+        // Regenerate it with `CHESS_AI_UPDATE_HEADER=1 cargo build` after changing this file
+        let generated = include_str!(concat!(env!("OUT_DIR"), "/chess_ai.h"));
+        assert_eq!(
+            include_str!("../include/chess_ai.h"),
+            generated,
+            "include/chess_ai.h is out of date"
+        );
+    }
+
+    #[test]
+    fn panics() {
+        assert_eq!(guard(|| CHESS_AI_OK), CHESS_AI_OK);
+        assert_eq!(guard(|| panic!("inside the library")), CHESS_AI_PANIC);
+    }
+}
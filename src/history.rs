@@ -0,0 +1,252 @@
+use chess::{Board, ChessMove, Game, Piece};
+use std::str::FromStr;
+
+/// Where a game began, which `chess::Game` does not share: the board, and its halfmove clock, which a FEN gives but a
+/// `chess::Board` does not keep.
+#[derive(Copy, Clone, PartialEq, Eq, Debug, Default)]
+pub struct Start {
+    pub board: Board,
+    pub halfmove_clock: u16,
+}
+impl Start {
+    /// The start at the FEN, or `None` if it is not valid.  A FEN without a halfmove clock starts it at 0.
+    pub fn from_fen(fen: &str) -> Option<Self> {
+        let board = Board::from_str(fen).ok()?;
+        let halfmove_clock = fen
+            .split_whitespace()
+            .nth(4)
+            .and_then(|c| c.parse().ok())
+            .unwrap_or(0);
+        Some(Start {
+            board,
+            halfmove_clock,
+        })
+    }
+}
+
+/// Positions reached in the game and along the line being searched, for spotting repetitions and the fifty-move rule
+/// in a search, where a `chess::Board` alone has no memory.
+#[derive(Clone, Debug)]
+pub struct History {
+    /// Hash of each position and its halfmove clock (plies since the last capture or pawn move).
+    positions: Vec<(u64, u16)>,
+}
+impl History {
+    /// Start a history at the board.
+    pub fn new(board: &Board) -> Self {
+        History {
+            positions: vec![(board.get_hash(), 0)],
+        }
+    }
+
+    /// The history of the moves played from the start, or `None` if one of them is not legal there.
+    pub fn from_moves(start: &Start, moves: impl IntoIterator<Item = ChessMove>) -> Option<Self> {
+        let mut board = start.board;
+        let mut history = History {
+            positions: vec![(board.get_hash(), start.halfmove_clock)],
+        };
+        for m in moves {
+            if !board.legal(m) {
+                return None;
+            }
+            let next = board.make_move_new(m);
+            history.push(&board, m, &next);
+            board = next;
+        }
+        Some(history)
+    }
+
+    /// The history of the game's moves.  `chess::Game` does not share its starting position, so the moves are replayed
+    /// from `start` when given, or else from the standard start, when that reaches the current position.  Otherwise
+    /// the history starts now.
+    pub fn from_game(game: &Game, start: Option<&Start>) -> Self {
+        let moves: Vec<ChessMove> = game
+            .actions()
+            .iter()
+            .filter_map(|a| match a {
+                chess::Action::MakeMove(m) => Some(*m),
+                _ => None,
+            })
+            .collect();
+
+        let current = game.current_position().get_hash();
+        start
+            .into_iter()
+            .chain([&Start::default()])
+            .filter_map(|start| History::from_moves(start, moves.iter().copied()))
+            .find(|history| history.positions.last().unwrap().0 == current)
+            .unwrap_or_else(|| History::new(&game.current_position()))
+    }
+
+    /// Add the position after playing the move on the board.
+    pub fn push(&mut self, board: &Board, the_move: ChessMove, after: &Board) {
+        let irreversible = board.piece_on(the_move.get_source()) == Some(Piece::Pawn)
+            || board.piece_on(the_move.get_dest()).is_some();
+        let clock = if irreversible {
+            0
+        } else {
+            self.halfmove_clock() + 1
+        };
+        self.positions.push((after.get_hash(), clock));
+    }
+
+    /// Add the position after passing the turn, as null-move pruning does.  Nothing before a pass counts as a
+    /// repetition, since the line could not really happen.
+    pub fn push_null(&mut self, after: &Board) {
+        self.positions.push((after.get_hash(), 0));
+    }
+
+    /// Go back to the position before the last `push`.
+    pub fn pop(&mut self) {
+        self.positions.pop();
+    }
+
+    /// Plies since the last capture or pawn move.
+    pub fn halfmove_clock(&self) -> u16 {
+        self.positions.last().unwrap().1
+    }
+
+    /// How often the current position has been reached, counting this time.
+    pub fn repetitions(&self) -> usize {
+        let (hash, clock) = *self.positions.last().unwrap();
+        1 + self
+            .positions
+            .iter()
+            .rev()
+            .skip(1)
+            .take(clock as usize)
+            .filter(|(h, _)| *h == hash)
+            .count()
+    }
+
+    /// Whether the current position is a draw by the fifty-move rule or repeats an earlier one.  One repeat is enough
+    /// for a search, since the side that repeated can do it again.
+    pub fn is_draw(&self) -> bool {
+        let (hash, clock) = *self.positions.last().unwrap();
+        // Positions before the last capture or pawn move cannot come back
+        clock >= 100
+            || self
+                .positions
+                .iter()
+                .rev()
+                .skip(1)
+                .take(clock as usize)
+                .any(|(h, _)| *h == hash)
+    }
+}
+
+//==============================================================================
+#[cfg(test)]
+mod test {
+    use super::*;
+    use chess::Square;
+
+    /// Play the moves on the board, keeping the history
+    fn play(history: &mut History, board: &mut Board, moves: &[(Square, Square)]) {
+        for (from, to) in moves {
+            let m = ChessMove::new(*from, *to, None);
+            let next = board.make_move_new(m);
+            history.push(board, m, &next);
+            *board = next;
+        }
+    }
+
+    #[test]
+    fn repetition() {
+        let mut board = Board::default();
+        let mut history = History::new(&board);
+        play(
+            &mut history,
+            &mut board,
+            &[(Square::G1, Square::F3), (Square::G8, Square::F6)],
+        );
+        assert!(!history.is_draw());
+        assert_eq!(history.halfmove_clock(), 2);
+
+        // The knights go back to the starting position
+        play(
+            &mut history,
+            &mut board,
+            &[(Square::F3, Square::G1), (Square::F6, Square::G8)],
+        );
+        assert!(history.is_draw());
+        assert_eq!(history.repetitions(), 2);
+        history.pop();
+        assert!(!history.is_draw());
+        assert_eq!(history.repetitions(), 1);
+
+        // A pawn move resets the clock
+        play(&mut history, &mut board, &[(Square::E7, Square::E5)]);
+        assert_eq!(history.halfmove_clock(), 0);
+
+        // Nothing before a pass counts
+        history.push_null(&board.null_move().unwrap());
+        assert_eq!(history.halfmove_clock(), 0);
+        assert!(!history.is_draw());
+    }
+
+    #[test]
+    fn fifty_moves() {
+        // A position reached after 99 quiet plies
+        let mut board = Board::default();
+        let mut history = History {
+            positions: vec![(board.get_hash(), 99)],
+        };
+        assert!(!history.is_draw());
+        play(&mut history, &mut board, &[(Square::G1, Square::F3)]);
+        assert_eq!(history.halfmove_clock(), 100);
+        assert!(history.is_draw());
+    }
+
+    #[test]
+    fn from_game() {
+        let mut game = Game::new();
+        for (from, to) in [
+            (Square::G1, Square::F3),
+            (Square::G8, Square::F6),
+            (Square::F3, Square::G1),
+        ] {
+            assert!(game.make_move(ChessMove::new(from, to, None)));
+        }
+        let mut history = History::from_game(&game, None);
+        assert_eq!(history.halfmove_clock(), 3);
+        let (board, m) = (
+            game.current_position(),
+            ChessMove::new(Square::F6, Square::G8, None),
+        );
+        history.push(&board, m, &board.make_move_new(m));
+        assert!(history.is_draw());
+
+        // A game from another position starts its history there, unless told where it started
+        let start = Start::from_fen("7k/8/8/8/8/8/8/R6K w - - 30 60").unwrap();
+        assert_eq!(start.halfmove_clock, 30);
+        let mut game = Game::new_with_board(start.board);
+        let moves = [
+            (Square::A1, Square::B1),
+            (Square::H8, Square::G8),
+            (Square::B1, Square::A1),
+            (Square::G8, Square::H8),
+        ];
+        for (from, to) in moves {
+            assert!(game.make_move(ChessMove::new(from, to, None)));
+        }
+        assert_eq!(History::from_game(&game, None).halfmove_clock(), 0);
+        assert!(!History::from_game(&game, None).is_draw());
+        let history = History::from_game(&game, Some(&start));
+        assert_eq!(history.halfmove_clock(), 34);
+        assert!(history.is_draw());
+
+        // A start the game did not come from is ignored
+        let other = Start::from_fen("6k1/8/8/8/8/8/8/R6K w - - 0 1").unwrap();
+        assert_eq!(History::from_game(&game, Some(&other)).halfmove_clock(), 0);
+        assert_eq!(
+            Start::from_fen("7k/8/8/8/8/8/8/R6K w - -")
+                .unwrap()
+                .halfmove_clock,
+            0
+        );
+        assert!(
+            History::from_moves(&start, [ChessMove::new(Square::A2, Square::A3, None)]).is_none()
+        );
+    }
+}
@@ -0,0 +1,92 @@
+use crate::score::Score;
+use chess::ChessMove;
+use std::fmt;
+use std::time::Duration;
+
+/// Called with the progress of a search each time an iteration completes.
+pub type InfoCallback = Box<dyn FnMut(&SearchInfo) + Send>;
+
+/// What a search knows after an iteration: how deep it got, how good the position is, how much work it took and the
+/// line it expects.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct SearchInfo {
+    pub depth: u8,
+    /// Score for the side to move.
+    pub score: Score,
+    pub nodes: u64,
+    /// Nodes per second.
+    pub nps: u64,
+    pub elapsed: Duration,
+    /// How full the transposition table or tree budget is, in parts per thousand.
+    pub hashfull: u16,
+    /// The principal variation: the best move and the replies the search expects.
+    pub pv: Vec<ChessMove>,
+}
+impl SearchInfo {
+    /// Fill in the nodes per second from the nodes and elapsed time.
+    pub fn new(
+        depth: u8,
+        score: Score,
+        nodes: u64,
+        elapsed: Duration,
+        hashfull: u16,
+        pv: Vec<ChessMove>,
+    ) -> Self {
+        let nps = (u128::from(nodes) * 1_000_000 / elapsed.as_micros().max(1)) as u64;
+        SearchInfo {
+            depth,
+            score,
+            nodes,
+            nps,
+            elapsed,
+            hashfull,
+            pv,
+        }
+    }
+}
+impl fmt::Display for SearchInfo {
+    /// As in a UCI `info` line, such as "depth 3 score cp 35 nodes 1200 nps 60000 time 20 hashfull 4 pv e2e4 e7e5".
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "depth {} score {} nodes {} nps {} time {} hashfull {} pv",
+            self.depth,
+            self.score,
+            self.nodes,
+            self.nps,
+            self.elapsed.as_millis(),
+            self.hashfull
+        )?;
+        for m in &self.pv {
+            write!(f, " {m}")?;
+        }
+        Ok(())
+    }
+}
+
+//==============================================================================
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::str::FromStr;
+
+    #[test]
+    fn display() {
+        let pv = vec![
+            ChessMove::from_str("e2e4").unwrap(),
+            ChessMove::from_str("e7e5").unwrap(),
+        ];
+        let info = SearchInfo::new(3, Score::cp(35), 1200, Duration::from_millis(20), 4, pv);
+        assert_eq!(info.nps, 60000);
+        assert_eq!(
+            info.to_string(),
+            "depth 3 score cp 35 nodes 1200 nps 60000 time 20 hashfull 4 pv e2e4 e7e5"
+        );
+
+        // No time taken still gives a rate
+        assert_eq!(
+            SearchInfo::new(1, Score::mate_in(1), 5, Duration::ZERO, 0, Vec::new()).nps,
+            5_000_000
+        );
+    }
+}
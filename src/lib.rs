@@ -0,0 +1,160 @@
+//! Chess agents that pick moves by random choice, piece-square scoring or tree search, and a runner to play
+//! them against each other.
+pub mod agent_depth;
+pub mod agent_human;
+pub mod agent_mcts;
+pub mod agent_ponder;
+pub mod agent_random;
+pub mod agent_simple;
+pub mod agent_smp;
+pub mod agent_thread;
+pub mod epd;
+pub mod eval;
+pub mod ffi;
+pub mod history;
+pub mod info;
+pub mod limit;
+pub mod pawns;
+#[cfg(feature = "python")]
+mod python;
+pub mod runner;
+pub mod sampling;
+pub mod score;
+pub mod search;
+pub mod see;
+pub mod terms;
+pub mod threshold;
+pub mod trace;
+pub mod tree;
+pub mod tt;
+pub mod uci;
+pub use agent_depth::DepthAgent;
+pub use agent_human::HumanAgent;
+pub use agent_mcts::MctsAgent;
+pub use agent_ponder::PonderAgent;
+pub use agent_random::RandomAgent;
+pub use agent_simple::SimpleAgent;
+pub use agent_smp::{SearchStats, Selectivity, SmpAgent};
+pub use agent_thread::ThreadAgent;
+pub use eval::{Evaluator, Material, NodeState};
+pub use history::Start;
+pub use info::{InfoCallback, SearchInfo};
+pub use limit::Limit;
+pub use runner::{play_game, play_game_from, play_game_logged};
+pub use sampling::Sampling;
+pub use score::{Calc, Score};
+pub use terms::Weights;
+pub use threshold::Thresholds;
+
+/// What an agent decided to do on its turn.
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum Turn {
+    /// Play the move.
+    Move(chess::ChessMove),
+    /// Offer a draw and play the move.  The opponent may accept before it moves.
+    OfferDraw(chess::ChessMove),
+    /// Give up the game.
+    Resign,
+    /// Claim a draw by threefold repetition or the fifty-move rule.
+    ClaimDraw,
+    /// Take back the last move of each side.
+    TakeBack,
+}
+
+/// Common interface for player agents.
+pub trait Agent {
+    /// Given a game, return the best move.
+    fn make_move(&mut self, game: &chess::Game) -> Option<chess::ChessMove>;
+
+    /// Given a game, decide what to do.  By default, claim a draw when possible, otherwise play the best move or
+    /// resign if there is none.
+    fn take_turn(&mut self, game: &chess::Game) -> Turn {
+        if game.can_declare_draw() {
+            return Turn::ClaimDraw;
+        }
+        self.make_move(game).map_or(Turn::Resign, Turn::Move)
+    }
+
+    /// The opponent offered a draw with its last move; return true to accept.  By default, decline.
+    fn accept_draw(&mut self, _game: &chess::Game) -> bool {
+        false
+    }
+
+    /// Return a custom name for the implementation.
+    fn name(&self) -> String;
+
+    /// Report the progress of each search iteration to the callback.  Agents that do not search ignore it.
+    fn set_info_callback(&mut self, _callback: InfoCallback) {}
+
+    /// What the last search found, for agents that search.
+    fn last_info(&self) -> Option<SearchInfo> {
+        None
+    }
+
+    /// Share a flag that ends the search early when set, for searching in the background.  The move found by a
+    /// stopped search may be poor.  Agents that do not search ignore it.
+    fn set_stop_flag(&mut self, _stop: std::sync::Arc<std::sync::atomic::AtomicBool>) {}
+
+    /// Whether the stop flag cut the last search short, so its result is not from the whole search.  Agents that
+    /// ignore the flag are never stopped.
+    fn stopped(&self) -> bool {
+        false
+    }
+
+    /// Search to `depth` from now on, for deepening one agent a ply at a time.  Agents without a depth ignore it.
+    fn set_depth(&mut self, _depth: u8) {}
+
+    /// Whether a search to a depth deepens one ply at a time by itself, reporting each ply to the info callback and
+    /// answering from the last one it completed when stopped.
+    fn deepens(&self) -> bool {
+        false
+    }
+
+    /// The games given from now on began at `start`, which `chess::Game` does not record, so a search can find
+    /// repetitions and the fifty-move rule from there.  Agents that do not search ignore it.
+    fn set_start(&mut self, _start: Start) {}
+
+    /// The best `lines` moves, best first, each with its score and principal variation.  By default, only the move
+    /// `make_move` picks, with what the search found when there is a search.
+    fn analyze(&mut self, game: &chess::Game, _lines: usize) -> Vec<SearchInfo> {
+        let Some(m) = self.make_move(game) else {
+            return Vec::new();
+        };
+        let info = self.last_info().filter(|i| i.pv.first() == Some(&m));
+        vec![info.unwrap_or_else(|| {
+            SearchInfo::new(0, Score::ZERO, 0, std::time::Duration::ZERO, 0, vec![m])
+        })]
+    }
+}
+
+/// Create an agent from its command line name.  Search agents use the thresholds to resign and handle draws.
+/// "mcts" has no depth, so it runs 1000 playouts per level of depth instead.
+pub fn agent_by_name(name: &str, depth: u8, thresholds: Thresholds) -> Option<Box<dyn Agent>> {
+    match name {
+        "random" => Some(Box::new(RandomAgent::new())),
+        "simple" => Some(Box::new(SimpleAgent::new())),
+        "depth" => {
+            let mut agent = DepthAgent::new(depth);
+            agent.set_thresholds(thresholds);
+            Some(Box::new(agent))
+        }
+        "thread" => {
+            let mut agent = ThreadAgent::new(depth);
+            agent.set_thresholds(thresholds);
+            Some(Box::new(agent))
+        }
+        "smp" => {
+            let mut agent = SmpAgent::new(depth);
+            agent.set_thresholds(thresholds);
+            Some(Box::new(agent))
+        }
+        "mcts" => {
+            let mut agent = MctsAgent::new(agent_mcts::Budget::Iterations(
+                1000 * u32::from(depth.max(1)),
+            ));
+            agent.set_thresholds(thresholds);
+            Some(Box::new(agent))
+        }
+        _ => None,
+    }
+}
@@ -0,0 +1,274 @@
+use crate::info::SearchInfo;
+use crate::Agent;
+use chess::{ChessMove, Game};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{self, RecvTimeoutError, Sender};
+use std::sync::Arc;
+use std::thread::JoinHandle;
+use std::time::{Duration, Instant};
+
+/// Roughly how many times longer each ply takes than the one before.
+const BRANCHING: u32 = 4;
+
+/// How much effort to spend choosing a move.
+#[derive(Copy, Clone)]
+pub enum Limit {
+    /// Search to a fixed depth.
+    Depth(u8),
+    /// Deepen one ply at a time while there is time left, keeping the move from the last completed ply.
+    Time(Duration),
+}
+
+/// The move chosen within a limit, with the agent that chose it and the depth it searched.
+pub struct Choice {
+    pub the_move: Option<ChessMove>,
+    pub name: String,
+    pub depth: u8,
+}
+
+/// Sets a stop flag once its time is up, so a search ends at the deadline however deep it is.  Dropping it first
+/// leaves the flag alone.
+pub struct Deadline {
+    stop: Arc<AtomicBool>,
+    cancel: Option<Sender<()>>,
+    timer: Option<JoinHandle<()>>,
+}
+impl Deadline {
+    /// Set `stop` after `budget`.
+    pub fn new(budget: Duration, stop: Arc<AtomicBool>) -> Self {
+        let (cancel, cancelled) = mpsc::channel::<()>();
+        let flag = stop.clone();
+        let timer = std::thread::spawn(move || {
+            if let Err(RecvTimeoutError::Timeout) = cancelled.recv_timeout(budget) {
+                flag.store(true, Ordering::Relaxed);
+            }
+        });
+        Deadline {
+            stop,
+            cancel: Some(cancel),
+            timer: Some(timer),
+        }
+    }
+
+    /// The flag set at the deadline.
+    pub fn stop_flag(&self) -> Arc<AtomicBool> {
+        self.stop.clone()
+    }
+
+    pub fn passed(&self) -> bool {
+        self.stop.load(Ordering::Relaxed)
+    }
+}
+impl Drop for Deadline {
+    fn drop(&mut self) {
+        drop(self.cancel.take());
+        if let Some(timer) = self.timer.take() {
+            let _ = timer.join();
+        }
+    }
+}
+
+/// Choose a move with agents from `make_agent`, which is given the depth to search.
+pub fn choose_move(make_agent: &dyn Fn(u8) -> Box<dyn Agent>, game: &Game, limit: Limit) -> Choice {
+    let ((the_move, name), depth) = deepen(
+        limit,
+        |depth, stop| {
+            let mut agent = make_agent(depth);
+            agent.set_stop_flag(stop);
+            let the_move = agent.make_move(game);
+            ((the_move, agent.name()), agent.stopped())
+        },
+        |_| {},
+    );
+    Choice {
+        the_move,
+        name,
+        depth,
+    }
+}
+
+/// Choose a move with one agent, set to each depth in turn, so it keeps its configuration and what it learned at the
+/// shallower depths.  Afterwards the agent is at the depth it searched last, with a stop flag of its own.
+pub fn choose_move_with(agent: &mut dyn Agent, game: &Game, limit: Limit) -> Choice {
+    let (the_move, depth) = deepen(
+        limit,
+        |depth, stop| {
+            agent.set_depth(depth);
+            agent.set_stop_flag(stop);
+            let the_move = agent.make_move(game);
+            (the_move, agent.stopped())
+        },
+        |_| {},
+    );
+    agent.set_stop_flag(Arc::default());
+    Choice {
+        the_move,
+        name: agent.name(),
+        depth,
+    }
+}
+
+/// Find the best `lines` moves with agents from `make_agent`, best first, each with its score and principal
+/// variation.  `report` sees the lines found at each completed depth.
+pub fn analyze(
+    make_agent: &dyn Fn(u8) -> Box<dyn Agent>,
+    game: &Game,
+    limit: Limit,
+    lines: usize,
+    report: &mut dyn FnMut(&[SearchInfo]),
+) -> Vec<SearchInfo> {
+    let search = |depth, stop| {
+        let mut agent = make_agent(depth);
+        agent.set_stop_flag(stop);
+        let infos = agent.analyze(game, lines);
+        (infos, agent.stopped())
+    };
+    let (infos, _) = deepen(limit, search, |infos: &Vec<SearchInfo>| report(infos));
+    infos
+}
+
+/// Search at a fixed depth, or at increasing depths while there is time, returning the last completed result and its
+/// depth.  `search` is given the depth and a flag that stops it, and returns its result and whether the flag cut it
+/// short.  `completed` sees each result kept.
+fn deepen<T>(
+    limit: Limit,
+    mut search: impl FnMut(u8, Arc<AtomicBool>) -> (T, bool),
+    mut completed: impl FnMut(&T),
+) -> (T, u8) {
+    match limit {
+        Limit::Depth(depth) => {
+            let (result, _) = search(depth, Arc::default());
+            completed(&result);
+            (result, depth)
+        }
+        Limit::Time(budget) => {
+            let start = Instant::now();
+            let deadline = Deadline::new(budget, Arc::default());
+
+            // Always finish the first ply.  Each ply costs several times the last, so only start the next one when
+            // it is likely to finish in time, and throw it away if the deadline cuts it short.  A ply that finishes
+            // just as the deadline passes is kept.
+            let mut last = (search(1, Arc::default()).0, 1);
+            completed(&last.0);
+            let mut ply = start.elapsed();
+            while last.1 < u8::MAX && start.elapsed() + ply * BRANCHING < budget {
+                let iteration = Instant::now();
+                let (result, stopped) = search(last.1 + 1, deadline.stop_flag());
+                if stopped {
+                    break;
+                }
+                completed(&result);
+                last = (result, last.1 + 1);
+                ply = iteration.elapsed();
+            }
+            last
+        }
+    }
+}
+
+//==============================================================================
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::agent_depth::DepthAgent;
+
+    #[test]
+    fn depth_and_time() {
+        let game = Game::new();
+
+        let choice = choose_move(&|d| Box::new(DepthAgent::new(d)), &game, Limit::Depth(2));
+        assert_eq!(choice.name, "DepthAgent(2)");
+        assert_eq!(choice.depth, 2);
+        assert!(game.current_position().legal(choice.the_move.unwrap()));
+
+        // Always completes at least one ply, even with no time
+        let choice = choose_move(
+            &|d| Box::new(DepthAgent::new(d)),
+            &game,
+            Limit::Time(Duration::ZERO),
+        );
+        assert_eq!(choice.depth, 1);
+        assert!(game.current_position().legal(choice.the_move.unwrap()));
+
+        // Ends at the deadline, with the move from the last ply it completed
+        let start = Instant::now();
+        let choice = choose_move(
+            &|d| Box::new(DepthAgent::new(d)),
+            &game,
+            Limit::Time(Duration::from_millis(200)),
+        );
+        assert!(
+            start.elapsed() < Duration::from_millis(400),
+            "{:?}",
+            start.elapsed()
+        );
+        assert!(choice.depth >= 1);
+        assert!(game.current_position().legal(choice.the_move.unwrap()));
+    }
+
+    #[test]
+    fn one_agent() {
+        let game = Game::new();
+        let mut agent = DepthAgent::new(1);
+        let choice = choose_move_with(&mut agent, &game, Limit::Depth(3));
+        assert_eq!((choice.name.as_str(), choice.depth), ("DepthAgent(3)", 3));
+
+        // The deadline's flag is not left behind to stop the next search
+        let choice = choose_move_with(&mut agent, &game, Limit::Time(Duration::from_millis(50)));
+        assert!(game.current_position().legal(choice.the_move.unwrap()));
+        agent.set_depth(2);
+        assert!(agent.make_move(&game).is_some());
+        assert_eq!(agent.last_info().unwrap().depth, 2);
+    }
+
+    #[test]
+    fn late_ply() {
+        // A ply that finishes after the deadline is kept, and one the deadline stopped is not
+        for (stopped, depth) in [(false, 2), (true, 1)] {
+            let (result, searched) = deepen(
+                Limit::Time(Duration::from_millis(20)),
+                |d, stop| {
+                    if d > 1 {
+                        while !stop.load(Ordering::Relaxed) {
+                            std::thread::sleep(Duration::from_millis(1));
+                        }
+                    }
+                    (d, d > 1 && stopped)
+                },
+                |_| {},
+            );
+            assert_eq!((result, searched), (depth, depth));
+        }
+    }
+
+    #[test]
+    fn deadline() {
+        let stop = Arc::new(AtomicBool::new(false));
+        let deadline = Deadline::new(Duration::from_millis(20), stop.clone());
+        std::thread::sleep(Duration::from_millis(100));
+        assert!(deadline.passed() && stop.load(Ordering::Relaxed));
+
+        // Dropped in time, it leaves the flag alone
+        let stop = Arc::new(AtomicBool::new(false));
+        drop(Deadline::new(Duration::from_secs(60), stop.clone()));
+        assert!(!stop.load(Ordering::Relaxed));
+    }
+
+    #[test]
+    fn analyze_depths() {
+        let game = Game::new();
+        let mut depths = Vec::new();
+        let lines = analyze(
+            &|d| Box::new(DepthAgent::new(d)),
+            &game,
+            Limit::Depth(2),
+            2,
+            &mut |infos| {
+                depths.extend(infos.iter().map(|i| i.depth));
+            },
+        );
+        assert_eq!(depths, [2, 2]);
+        assert_eq!(lines.len(), 2);
+        assert_ne!(lines[0].pv[0], lines[1].pv[0]);
+    }
+}
@@ -1,72 +1,171 @@
-use chess::{Game, GameResult};
-mod agent_depth;
-mod agent_random;
-mod agent_simple;
-mod agent_thread;
-mod score;
-use agent_depth::DepthAgent;
-use agent_random::RandomAgent;
-use agent_simple::SimpleAgent;
-use agent_thread::ThreadAgent;
-
-/// Common interface for player agents.
-trait Agent {
-    /// Given a game, return the best move.
-    fn make_move(&mut self, game: &chess::Game) -> Option<chess::ChessMove>;
-
-    /// Return a custom name for the implementation.
-    fn name(&self) -> String;
-}
+use chess::{Board, Game};
+use chess_ai::{
+    agent_by_name, agent_human::render, epd, limit, play_game, uci::Uci, Calc, DepthAgent,
+    HumanAgent, Limit, RandomAgent, Score, SimpleAgent, Start, ThreadAgent, Thresholds, Weights,
+};
+use std::str::FromStr;
 
 fn main() {
-    play_game(Box::new(SimpleAgent::new()), Box::new(RandomAgent::new()), 100);
-    play_game(Box::new(RandomAgent::new()), Box::new(SimpleAgent::new()), 100);
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    match args.first().map(String::as_str) {
+        Some("epd") => return run_epd(&args[1..]),
+        Some("analyze") => return run_analyze(&args[1..]),
+        Some("play") => return run_play(&args[1..]),
+        Some("uci") => return run_uci(&args[1..]),
+        Some("eval") => return run_eval(&args[1..]),
+        _ => {}
+    }
+
+    play_game(
+        Box::new(SimpleAgent::new()),
+        Box::new(RandomAgent::new()),
+        100,
+    );
+    play_game(
+        Box::new(RandomAgent::new()),
+        Box::new(SimpleAgent::new()),
+        100,
+    );
     // play_game(Box::new(ThreadAgent::new(3)), Box::new(RandomAgent::new()), 100);
-    play_game(Box::new(ThreadAgent::new(3)), Box::new(DepthAgent::new(1)), 100);
+    play_game(
+        Box::new(ThreadAgent::new(3)),
+        Box::new(DepthAgent::new(1)),
+        100,
+    );
     // play_game(Box::new(ThreadAgent::new(4)), Box::new(RandomAgent::new()), 100);
 }
 
-/// Run the specified number of games with the player agents provided.
-#[allow(clippy::cast_precision_loss)] // for u32 to f32
-fn play_game(mut white: Box<dyn Agent>, mut black: Box<dyn Agent>, iterations: u32) {
-    let mut wins = [0, 0, 0];
-    let mut moves: Vec<f32> = Vec::new();
+/// Run an EPD test suite: `epd <file> [agent] [depth <n> | time <ms>]`
+fn run_epd(args: &[String]) {
+    let usage = "usage: epd <file> [random|simple|depth|thread|smp|mcts] [depth <n> | time <ms>]";
+    let Some(path) = args.first() else {
+        println!("{usage}");
+        return;
+    };
+    let agent = args.get(1).map_or("thread", String::as_str);
+    let Some(limit) = parse_limit(&args[2.min(args.len())..]) else {
+        println!("{usage}");
+        return;
+    };
+    if agent_by_name(agent, 1, Thresholds::default()).is_none() {
+        println!("{usage}");
+        return;
+    }
 
-    // Output the agent names so we know who is playing
-    println!("{} vs {}", white.name(), black.name());
+    match epd::load(path) {
+        Ok(positions) => epd::run_suite(
+            &|d| agent_by_name(agent, d, Thresholds::default()).unwrap(),
+            &positions,
+            limit,
+        )
+        .print(),
+        Err(e) => println!("{e}"),
+    }
+}
 
-    // Play the requested number of games
-    let start = std::time::Instant::now();
-    for _i in 0..iterations {
-        let mut move_count = 0.0;
-        let mut game = Game::new();
+/// Play a single game against an agent at the terminal: `play [agent] [depth] [white|black] [ascii]`
+fn run_play(args: &[String]) {
+    let name = args.first().map_or("thread", String::as_str);
+    let depth = args.get(1).and_then(|d| d.parse().ok()).unwrap_or(3);
+    let unicode = !args.iter().any(|a| a == "ascii");
+    // The opponent resigns a lost game, and offers or accepts a draw in an even one
+    let thresholds = Thresholds {
+        resign: Some(Score::cp(-500)),
+        draw: Some(Score::cp(10)),
+    };
+    let (Some(opponent), Some(hint)) = (
+        agent_by_name(name, depth, thresholds),
+        agent_by_name(name, depth, Thresholds::default()),
+    ) else {
+        println!("usage: play [random|simple|depth|thread|smp|mcts] [depth] [white|black] [ascii]");
+        return;
+    };
 
-        while game.result().is_none() {
-            // Asserts are there to ensure a valid move was given and made
-            if game.side_to_move() == chess::Color::White {
-                assert!(game.make_move(white.make_move(&game).unwrap()));
-            } else {
-                assert!(game.make_move(black.make_move(&game).unwrap()));
-            }
-            move_count += 1.0;
+    let human = Box::new(HumanAgent::stdio(unicode, Some(hint)));
+    if args.iter().any(|a| a == "black") {
+        play_game(opponent, human, 1);
+    } else {
+        play_game(human, opponent, 1);
+    }
+}
+
+/// Speak UCI on stdin and stdout, for a chess GUI: `uci [agent]`
+fn run_uci(args: &[String]) {
+    let name = args.first().map_or("smp", String::as_str);
+    match Uci::new(name, std::io::stdout()) {
+        Some(mut engine) => engine.run(std::io::stdin().lock()),
+        None => println!("usage: uci [random|simple|depth|thread|smp|mcts]"),
+    }
+}
 
-            // This library requires you to declare a draw vs being automatic
-            if game.can_declare_draw() {
-                game.declare_draw();
-            }
-        }
-        moves.push(move_count);
+/// Show the best few moves with their scores and lines: `analyze <fen|startpos> [agent] [lines] [depth <n> | time <ms>]`
+fn run_analyze(args: &[String]) {
+    let usage = "usage: analyze <fen|startpos> [random|simple|depth|thread|smp|mcts] [lines] [depth <n> | time <ms>]";
+    let start = match args.first().map(String::as_str) {
+        Some("startpos") => Some(Start::default()),
+        Some(fen) => Start::from_fen(fen),
+        None => None,
+    };
+    let agent = args.get(1).map_or("smp", String::as_str);
+    let lines = args.get(2).map_or(Some(3), |n| n.parse().ok());
+    let (Some(start), Some(lines), Some(limit), Some(_)) = (
+        start,
+        lines,
+        parse_limit(&args[3.min(args.len())..]),
+        agent_by_name(agent, 1, Thresholds::default()),
+    ) else {
+        println!("{usage}");
+        return;
+    };
 
-        match game.result().unwrap() {
-            GameResult::WhiteCheckmates | GameResult::BlackResigns => wins[0] += 1,
-            GameResult::WhiteResigns | GameResult::BlackCheckmates => wins[1] += 1,
-            _ => wins[2] += 1,
-        }
+    let game = Game::new_with_board(start.board);
+    let make_agent = |d| {
+        let mut agent = agent_by_name(agent, d, Thresholds::default()).unwrap();
+        agent.set_start(start);
+        agent
+    };
+    let infos = limit::analyze(&make_agent, &game, limit, lines, &mut |_| {});
+    for (i, info) in infos.iter().enumerate() {
+        let pv: Vec<String> = info.pv.iter().map(ToString::to_string).collect();
+        println!(
+            "{}. {:<10} depth {}  {}",
+            i + 1,
+            info.score.to_string(),
+            info.depth,
+            pv.join(" ")
+        );
     }
+}
+
+/// Show the board and how its score breaks down by piece and term: `eval <fen|startpos> [ascii]`
+fn run_eval(args: &[String]) {
+    let board = match args.first().map(String::as_str) {
+        Some("startpos") => Some(Board::default()),
+        Some(fen) => Board::from_str(fen).ok(),
+        None => None,
+    };
+    let Some(board) = board else {
+        println!("usage: eval <fen|startpos> [ascii]");
+        return;
+    };
+    let unicode = !args.iter().any(|a| a == "ascii");
+    print!("{}", render(&board, chess::Color::White, unicode));
+    println!();
+    // Every term, so the breakdown shows them all
+    let mut calc = Calc::with_pawn_structure();
+    calc.set_weights(Weights::default());
+    println!("{}", calc.trace(&board));
+}
 
-    // Calculate the averages (use full turns, where white + black = 1 full turn)
-    let elapsed = start.elapsed();
-    let m_avg: f32 = moves.iter().sum::<f32>() / iterations as f32 / 2.0;
-    let d_avg = elapsed / iterations;
-    println!("  {:?}, avg {:.1} full turns, avg {:.2?}, total {:.2?}", wins, m_avg, d_avg, elapsed);
+/// A search limit from `[depth <n> | time <ms>]`, searching three plies when there is none.
+fn parse_limit(args: &[String]) -> Option<Limit> {
+    match (
+        args.first().map(String::as_str),
+        args.get(1).and_then(|n| n.parse::<u64>().ok()),
+    ) {
+        (None, _) => Some(Limit::Depth(3)),
+        (Some("depth"), Some(n)) => Some(Limit::Depth(u8::try_from(n).unwrap_or(u8::MAX))),
+        (Some("time"), Some(n)) => Some(Limit::Time(std::time::Duration::from_millis(n))),
+        _ => None,
+    }
 }
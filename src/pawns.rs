@@ -0,0 +1,301 @@
+use crate::score::Tapered;
+use chess::{BitBoard, Board, Color, Piece, Rank, Square};
+use std::sync::atomic::{AtomicU64, Ordering};
+
+/// A pawn with another pawn of its side in front of it on the file.
+pub const DOUBLED: Tapered = Tapered::new(-10, -25);
+/// A pawn with no pawns of its side on the files beside it.
+pub const ISOLATED: Tapered = Tapered::new(-10, -15);
+/// A pawn behind the pawns of its side on the files beside it, whose next square an enemy pawn guards.
+pub const BACKWARD: Tapered = Tapered::new(-8, -12);
+/// A pawn beside or guarded by another pawn of its side, by how far it has advanced (ranks 1 to 8 from its side).
+pub const CONNECTED: [i32; 8] = [0, 2, 3, 5, 8, 14, 24, 0];
+/// A pawn no enemy pawn can stop, by how far it has advanced, worth more as pieces come off.
+pub const PASSED: [Tapered; 8] = [
+    Tapered::new(0, 0),
+    Tapered::new(5, 10),
+    Tapered::new(5, 15),
+    Tapered::new(10, 25),
+    Tapered::new(20, 45),
+    Tapered::new(35, 75),
+    Tapered::new(60, 120),
+    Tapered::new(0, 0),
+];
+
+/// Entries in a pawn hash table, which few pawn structures fill since pawns move rarely.
+const PAWN_TABLE_SLOTS: usize = 1 << 14;
+
+/// The pawn structure terms of one side, each summed over its pawns.
+#[derive(Copy, Clone, Default, PartialEq, Eq, Debug)]
+pub struct PawnTerms {
+    pub doubled: Tapered,
+    pub isolated: Tapered,
+    pub backward: Tapered,
+    pub connected: Tapered,
+    pub passed: Tapered,
+}
+impl PawnTerms {
+    /// The terms for the side's pawns.
+    pub fn of(board: &Board, side: Color) -> Self {
+        let ours = board.pieces(Piece::Pawn) & board.color_combined(side);
+        let theirs = board.pieces(Piece::Pawn) & board.color_combined(!side);
+        let mut terms = PawnTerms::default();
+        for square in ours {
+            let (file, rank) = (square.get_file(), relative_rank(square, side));
+            let beside = chess::get_adjacent_files(file) & ours;
+            let ahead = in_front(square, side);
+            if (chess::get_file(file) & ahead & ours) != chess::EMPTY {
+                terms.doubled += DOUBLED;
+            }
+            if beside == chess::EMPTY {
+                terms.isolated += ISOLATED;
+            } else if (beside & !ahead) == chess::EMPTY {
+                // Every pawn beside it has gone ahead, so none can come up to guard it, and it cannot safely advance
+                let next = square.forward(side);
+                if next
+                    .is_some_and(|next| chess::get_pawn_attacks(next, side, theirs) != chess::EMPTY)
+                {
+                    terms.backward += BACKWARD;
+                }
+            }
+            let phalanx = beside & chess::get_rank(square.get_rank());
+            let guards = chess::get_pawn_attacks(square, !side, ours);
+            if (phalanx | guards) != chess::EMPTY {
+                terms.connected += Tapered::new(CONNECTED[rank], CONNECTED[rank]);
+            }
+            let stoppers =
+                (chess::get_file(file) | chess::get_adjacent_files(file)) & ahead & theirs;
+            if stoppers == chess::EMPTY {
+                terms.passed += PASSED[rank];
+            }
+        }
+        terms
+    }
+
+    pub fn total(&self) -> Tapered {
+        self.doubled + self.isolated + self.backward + self.connected + self.passed
+    }
+
+    /// Each term with its name, for showing them.
+    pub fn named(&self) -> [(&'static str, Tapered); 5] {
+        [
+            ("doubled pawns", self.doubled),
+            ("isolated pawns", self.isolated),
+            ("backward pawns", self.backward),
+            ("connected pawns", self.connected),
+            ("passed pawns", self.passed),
+        ]
+    }
+}
+
+/// The pawn structure for White less that for Black.
+pub fn pawn_structure(board: &Board) -> Tapered {
+    PawnTerms::of(board, Color::White).total() - PawnTerms::of(board, Color::Black).total()
+}
+
+/// Rank of the square counted from the side's first rank, 0 to 7.
+pub(crate) fn relative_rank(square: Square, side: Color) -> usize {
+    match side {
+        Color::White => square.get_rank().to_index(),
+        Color::Black => 7 - square.get_rank().to_index(),
+    }
+}
+
+/// Every square on the ranks in front of the square, from the side's point of view.
+pub(crate) fn in_front(square: Square, side: Color) -> BitBoard {
+    let rank = square.get_rank().to_index();
+    let ranks = match side {
+        Color::White => rank + 1..8,
+        Color::Black => 0..rank,
+    };
+    ranks.fold(chess::EMPTY, |ahead, r| {
+        ahead | chess::get_rank(Rank::from_index(r))
+    })
+}
+
+/// Random keys for a pawn of each color on each square, from a fixed seed so keys are the same every run.
+/// `chess::Board::get_pawn_hash` is not implemented, so the pawn hash is built here.
+const PAWN_KEYS: [[u64; 64]; 2] = {
+    let mut keys = [[0; 64]; 2];
+    let mut state: u64 = 0x5eed_0f9a_3e51;
+    let mut i = 0;
+    while i < 128 {
+        // SplitMix64
+        state = state.wrapping_add(0x9e37_79b9_7f4a_7c15);
+        let mut z = state;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        keys[i / 64][i % 64] = z ^ (z >> 31);
+        i += 1;
+    }
+    keys
+};
+
+/// A Zobrist key of the pawns alone, the same for every position with the same pawns.
+pub fn pawn_key(board: &Board) -> u64 {
+    let mut key = 0;
+    for color in chess::ALL_COLORS {
+        for square in board.pieces(Piece::Pawn) & board.color_combined(color) {
+            key ^= PAWN_KEYS[color.to_index()][square.to_index()];
+        }
+    }
+    key
+}
+
+/// A cache of pawn structure scores by pawn key, which many search threads share without locks, as the
+/// transposition table does.
+pub struct PawnTable {
+    slots: Vec<[AtomicU64; 2]>,
+}
+impl PawnTable {
+    pub fn new() -> Self {
+        PawnTable {
+            slots: (0..PAWN_TABLE_SLOTS)
+                .map(|_| [AtomicU64::new(0), AtomicU64::new(0)])
+                .collect(),
+        }
+    }
+
+    /// The pawn structure of the board, from the table when it has the board's pawns.
+    pub fn structure(&self, board: &Board) -> Tapered {
+        let key = pawn_key(board);
+        self.probe(key).unwrap_or_else(|| {
+            let structure = pawn_structure(board);
+            self.store(key, structure);
+            structure
+        })
+    }
+
+    pub fn probe(&self, key: u64) -> Option<Tapered> {
+        let slot = &self.slots[key as usize & (PAWN_TABLE_SLOTS - 1)];
+        let data = slot[1].load(Ordering::Relaxed);
+        (data != 0 && slot[0].load(Ordering::Relaxed) ^ data == key)
+            .then(|| Tapered::new(i32::from(data as i16), i32::from((data >> 16) as i16)))
+    }
+
+    pub fn store(&self, key: u64, structure: Tapered) {
+        let clamp = |v: i32| u64::from(v.clamp(i16::MIN.into(), i16::MAX.into()) as i16 as u16);
+        // The top bit marks the slot as used, since a structure could otherwise pack to zero
+        let data = clamp(structure.middlegame) | clamp(structure.endgame) << 16 | 1 << 63;
+        let slot = &self.slots[key as usize & (PAWN_TABLE_SLOTS - 1)];
+        slot[0].store(key ^ data, Ordering::Relaxed);
+        slot[1].store(data, Ordering::Relaxed);
+    }
+}
+impl Default for PawnTable {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+//==============================================================================
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::str::FromStr;
+
+    fn white(fen: &str) -> PawnTerms {
+        PawnTerms::of(&Board::from_str(fen).unwrap(), Color::White)
+    }
+
+    #[test]
+    fn doubled() {
+        // Only the pawn behind counts, and a third on the file counts again
+        assert_eq!(white("4k3/8/8/8/4P3/4P3/8/4K3 w - - 0 1").doubled, DOUBLED);
+        assert_eq!(
+            white("4k3/8/8/4P3/4P3/4P3/8/4K3 w - - 0 1").doubled,
+            DOUBLED * 2
+        );
+        assert_eq!(
+            white("4k3/8/8/8/3P4/4P3/8/4K3 w - - 0 1").doubled,
+            Tapered::default()
+        );
+    }
+
+    #[test]
+    fn isolated() {
+        // The a-pawn has no neighbours, while the d and e pawns have each other
+        let terms = white("4k3/8/8/8/P7/3PP3/8/4K3 w - - 0 1");
+        assert_eq!(terms.isolated, ISOLATED);
+        assert_eq!(
+            white("4k3/8/8/8/P1P5/8/8/4K3 w - - 0 1").isolated,
+            ISOLATED * 2
+        );
+    }
+
+    #[test]
+    fn backward() {
+        // The d-pawn's neighbours have gone ahead and a black pawn guards d4, which it cannot do without the black pawn
+        assert_eq!(
+            white("4k3/8/8/4p3/2P1P3/3P4/8/4K3 w - - 0 1").backward,
+            BACKWARD
+        );
+        assert_eq!(
+            white("4k3/8/8/8/2P1P3/3P4/8/4K3 w - - 0 1").backward,
+            Tapered::default()
+        );
+        // Nor with a neighbour level with it
+        assert_eq!(
+            white("4k3/8/8/4p3/2P5/3PP3/8/4K3 w - - 0 1").backward,
+            Tapered::default()
+        );
+    }
+
+    #[test]
+    fn connected() {
+        // Side by side on the fourth rank, and a chain guarding the pawn ahead on the fifth
+        assert_eq!(
+            white("4k3/8/8/8/3PP3/8/8/4K3 w - - 0 1").connected,
+            Tapered::new(10, 10)
+        );
+        assert_eq!(
+            white("4k3/8/8/4P3/3P4/8/8/4K3 w - - 0 1").connected,
+            Tapered::new(8, 8)
+        );
+        assert_eq!(
+            white("4k3/8/8/8/P6P/8/8/4K3 w - - 0 1").connected,
+            Tapered::default()
+        );
+    }
+
+    #[test]
+    fn passed() {
+        // Nothing in front of the b-pawn, which is worth more further up and in the endgame
+        let terms = white("4k3/6p1/1P6/8/8/8/5PPP/4K3 w - - 0 1");
+        assert_eq!(terms.passed, PASSED[5]);
+        assert!(PASSED[6].blend(0) > PASSED[6].blend(24));
+        assert!(PASSED[6].endgame > PASSED[5].endgame);
+
+        // An enemy pawn in front or on a file beside it stops it
+        assert_eq!(
+            white("4k3/2p5/1P6/8/8/8/8/4K3 w - - 0 1").passed,
+            Tapered::default()
+        );
+        assert_eq!(
+            white("4k3/1p6/1P6/8/8/8/8/4K3 w - - 0 1").passed,
+            Tapered::default()
+        );
+        // A black pawn passes from its own side
+        let board = Board::from_str("4k3/8/8/8/8/6p1/8/4K3 w - - 0 1").unwrap();
+        assert_eq!(PawnTerms::of(&board, Color::Black).passed, PASSED[5]);
+    }
+
+    #[test]
+    fn symmetry_and_cache() {
+        // The start is even, and a mirrored structure scores the other way round
+        assert_eq!(pawn_structure(&Board::default()), Tapered::default());
+        let left = Board::from_str("4k3/p7/8/8/3P4/3P4/8/4K3 w - - 0 1").unwrap();
+        let mirrored = Board::from_str("4k3/8/3p4/3p4/8/8/P7/4K3 w - - 0 1").unwrap();
+        assert_eq!(pawn_structure(&left), -pawn_structure(&mirrored));
+
+        // The key depends on the pawns only, and the table returns what it computed
+        let moved = Board::from_str("3k4/p7/8/8/3P4/3P4/8/3K4 w - - 0 1").unwrap();
+        assert_eq!(pawn_key(&left), pawn_key(&moved));
+        assert_ne!(pawn_key(&left), pawn_key(&mirrored));
+        let table = PawnTable::new();
+        assert_eq!(table.probe(pawn_key(&left)), None);
+        assert_eq!(table.structure(&left), pawn_structure(&left));
+        assert_eq!(table.probe(pawn_key(&left)), Some(pawn_structure(&left)));
+        assert_eq!(table.structure(&moved), pawn_structure(&left));
+    }
+}
@@ -0,0 +1,373 @@
+// The pyo3 0.22 macros convert every PyResult error into PyErr, which clippy reports on each function
+#![allow(clippy::useless_conversion)]
+
+use crate::agent_human::parse_move;
+use crate::{agent_by_name, play_game, Agent, Calc, Start, Thresholds};
+use chess::{Board, ChessMove, Game, MoveGen};
+use pyo3::exceptions::PyValueError;
+use pyo3::prelude::*;
+use pyo3::types::PyDict;
+use std::cell::RefCell;
+use std::rc::Rc;
+use std::str::FromStr;
+
+/// The first error from a Python agent in a match, which `play_match` raises once the games are over.
+type Failure = Rc<RefCell<Option<PyErr>>>;
+
+/// A Python object with a `make_move(fen)` method, playing through the Rust `Agent` trait.
+/// The method returns a move in UCI or SAN notation, or `None` to resign.  If it raises or plays an illegal move, the
+/// agent resigns and keeps the error, and from then on resigns without asking.
+struct PythonAgent {
+    object: PyObject,
+    failure: Failure,
+}
+impl Agent for PythonAgent {
+    fn make_move(&mut self, game: &Game) -> Option<ChessMove> {
+        if self.failure.borrow().is_some() {
+            return None;
+        }
+        let board = game.current_position();
+        let the_move = Python::with_gil(|py| {
+            let text: Option<String> = self
+                .object
+                .call_method1(py, "make_move", (board.to_string(),))?
+                .extract(py)?;
+            text.map(|t| {
+                parse_move(&board, &t).ok_or_else(|| {
+                    PyValueError::new_err(format!("{} played an illegal move {t}", self.name()))
+                })
+            })
+            .transpose()
+        });
+        the_move.unwrap_or_else(|e| {
+            *self.failure.borrow_mut() = Some(e);
+            None
+        })
+    }
+
+    fn name(&self) -> String {
+        Python::with_gil(|py| {
+            let object = self.object.bind(py);
+            match object.getattr("name") {
+                Ok(n) if n.is_callable() => n.call0().and_then(|n| n.extract()).ok(),
+                Ok(n) => n.extract().ok(),
+                Err(_) => None,
+            }
+            .or_else(|| object.get_type().name().ok().map(|n| n.to_string()))
+            .unwrap_or_else(|| "PythonAgent".to_string())
+        })
+    }
+}
+
+/// Turn a Python argument into an agent: either an agent name searching to `depth`, or an object with `make_move`,
+/// whose errors go to `failure`.
+fn to_agent(player: &Bound<'_, PyAny>, depth: u8, failure: &Failure) -> PyResult<Box<dyn Agent>> {
+    if let Ok(name) = player.extract::<String>() {
+        return agent_by_name(&name, depth, Thresholds::default())
+            .ok_or_else(|| PyValueError::new_err(format!("Unknown agent {name}")));
+    }
+    if player.hasattr("make_move")? {
+        return Ok(Box::new(PythonAgent {
+            object: player.clone().unbind(),
+            failure: failure.clone(),
+        }));
+    }
+    Err(PyValueError::new_err(
+        "An agent must be a name or have a make_move(fen) method",
+    ))
+}
+
+fn to_board(fen: &str) -> PyResult<Board> {
+    Board::from_str(fen).map_err(|e| PyValueError::new_err(e.to_string()))
+}
+
+/// A Rust agent for playing a whole game from Python.  It keeps the game so far, so when the next position follows
+/// its own last move by one legal reply, the search continues from its tree and knows the repetitions.
+#[pyclass(name = "Agent", unsendable)]
+struct PyAgent {
+    agent: Box<dyn Agent>,
+    game: Option<Game>,
+}
+#[pymethods]
+impl PyAgent {
+    #[new]
+    #[pyo3(signature = (name="thread", depth=3))]
+    fn new(name: &str, depth: u8) -> PyResult<Self> {
+        agent_by_name(name, depth, Thresholds::default())
+            .map(|agent| PyAgent { agent, game: None })
+            .ok_or_else(|| PyValueError::new_err(format!("Unknown agent {name}")))
+    }
+
+    /// The best move for the FEN position in UCI notation, or None if there is no legal move.
+    fn make_move(&mut self, fen: &str) -> PyResult<Option<String>> {
+        to_board(fen)?;
+        self.follow(Start::from_fen(fen).unwrap());
+        let game = self.game.as_mut().unwrap();
+        let the_move = self.agent.make_move(game);
+        if let Some(m) = the_move {
+            game.make_move(m);
+        }
+        Ok(the_move.map(|m| m.to_string()))
+    }
+
+    fn name(&self) -> String {
+        self.agent.name()
+    }
+}
+impl PyAgent {
+    /// Continue the game by the opponent's reply that reaches the board, or start a new game there.
+    fn follow(&mut self, start: Start) {
+        let reply = self.game.as_ref().and_then(|game| {
+            let position = game.current_position();
+            MoveGen::new_legal(&position).find(|m| position.make_move_new(*m) == start.board)
+        });
+        match (reply, &mut self.game) {
+            (Some(m), Some(game)) => {
+                game.make_move(m);
+            }
+            _ => {
+                self.game = Some(Game::new_with_board(start.board));
+                self.agent.set_start(start);
+            }
+        }
+    }
+}
+
+/// The piece-square evaluation, exposed as a class so the tables are built once.
+#[pyclass(name = "Calc", frozen)]
+struct PyCalc {
+    calc: Calc,
+}
+#[pymethods]
+impl PyCalc {
+    #[new]
+    fn new() -> Self {
+        PyCalc { calc: Calc::new() }
+    }
+
+    /// Score the FEN position for white (`white=True`) or black.
+    #[pyo3(signature = (fen, white=true))]
+    fn score_board(&self, fen: &str, white: bool) -> PyResult<i32> {
+        Ok(self.calc.score_board(&to_board(fen)?, white).centipawns())
+    }
+
+    /// Score the FEN position after the UCI move, for white (`white=True`) or black.
+    #[pyo3(signature = (fen, uci, white=true))]
+    fn score_move(&self, fen: &str, uci: &str, white: bool) -> PyResult<i32> {
+        let board = to_board(fen)?;
+        let the_move = parse_move(&board, uci)
+            .ok_or_else(|| PyValueError::new_err(format!("Illegal move {uci}")))?;
+        Ok(self
+            .calc
+            .score_move(
+                &board,
+                white,
+                the_move,
+                self.calc.score_board(&board, white),
+            )
+            .centipawns())
+    }
+}
+
+/// The best move for the FEN position in UCI notation, or None if there is no legal move.
+#[pyfunction]
+#[pyo3(signature = (fen, agent="thread", depth=3))]
+fn best_move(fen: &str, agent: &str, depth: u8) -> PyResult<Option<String>> {
+    PyAgent::new(agent, depth)?.make_move(fen)
+}
+
+/// Score the FEN position for the side to move.
+#[pyfunction]
+fn evaluate(fen: &str) -> PyResult<i32> {
+    let board = to_board(fen)?;
+    Ok(Calc::new()
+        .score_board(&board, board.side_to_move() == chess::Color::White)
+        .centipawns())
+}
+
+/// Play games between two agents, each an agent name or a Python object with `make_move(fen)`.
+#[pyfunction]
+#[pyo3(signature = (white, black, games=1, depth=3))]
+fn play_match<'py>(
+    py: Python<'py>,
+    white: &Bound<'py, PyAny>,
+    black: &Bound<'py, PyAny>,
+    games: u32,
+    depth: u8,
+) -> PyResult<Bound<'py, PyDict>> {
+    let failure = Failure::default();
+    let (white, black) = (
+        to_agent(white, depth, &failure)?,
+        to_agent(black, depth, &failure)?,
+    );
+    let names = (white.name(), black.name());
+    let wins = play_game(white, black, games);
+    if let Some(e) = failure.take() {
+        return Err(e);
+    }
+
+    let result = PyDict::new_bound(py);
+    result.set_item("white", names.0)?;
+    result.set_item("black", names.1)?;
+    result.set_item("games", games)?;
+    result.set_item("white_wins", wins[0])?;
+    result.set_item("black_wins", wins[1])?;
+    result.set_item("draws", wins[2])?;
+    Ok(result)
+}
+
+/// The `chess_ai` Python module.
+#[pymodule]
+fn chess_ai(m: &Bound<'_, PyModule>) -> PyResult<()> {
+    m.add_class::<PyAgent>()?;
+    m.add_class::<PyCalc>()?;
+    m.add_function(wrap_pyfunction!(best_move, m)?)?;
+    m.add_function(wrap_pyfunction!(evaluate, m)?)?;
+    m.add_function(wrap_pyfunction!(play_match, m)?)?;
+    Ok(())
+}
+
+//==============================================================================
+#[cfg(test)]
+mod test {
+    use super::*;
+    use pyo3::types::PyModule;
+
+    /// Load the module and Python agent classes: one that asks a Rust agent for its moves, one that raises and one that
+    /// plays an illegal move.
+    fn with_module<F: FnOnce(&Bound<'_, PyModule>, &Bound<'_, PyModule>)>(f: F) {
+        // The module can only be added before the interpreter starts, once for all the tests
+        static INIT: std::sync::Once = std::sync::Once::new();
+        INIT.call_once(|| {
+            pyo3::append_to_inittab!(chess_ai);
+            pyo3::prepare_freethreaded_python();
+        });
+        Python::with_gil(|py| {
+            let module = PyModule::import_bound(py, "chess_ai").unwrap();
+            let code = "
+import chess_ai
+class Mover:
+    def __init__(self):
+        self.inner = chess_ai.Agent('simple')
+    def name(self):
+        return 'Mover'
+    def make_move(self, fen):
+        return self.inner.make_move(fen)
+class Raiser:
+    def make_move(self, fen):
+        raise KeyError('no move')
+class Cheater:
+    def make_move(self, fen):
+        return 'e2e5'
+";
+            let agents = PyModule::from_code_bound(py, code, "agents.py", "agents").unwrap();
+            f(&module, &agents);
+        });
+    }
+
+    #[test]
+    fn python_module() {
+        with_module(|m, agents| {
+            let mover = agents.getattr("Mover").unwrap().call0().unwrap();
+            let start = Board::default().to_string();
+
+            let best: Option<String> = m
+                .call_method1("best_move", (&start, "depth", 2))
+                .unwrap()
+                .extract()
+                .unwrap();
+            assert!(Board::default().legal(ChessMove::from_str(&best.unwrap()).unwrap()));
+            assert!(m.call_method1("best_move", (&start, "nobody", 2)).is_err());
+            assert!(m.call_method1("evaluate", ("not a fen",)).is_err());
+            assert_eq!(
+                m.call_method1("evaluate", (&start,))
+                    .unwrap()
+                    .extract::<i32>()
+                    .unwrap(),
+                0
+            );
+
+            let calc = m.getattr("Calc").unwrap().call0().unwrap();
+            let score: i32 = calc
+                .call_method1("score_move", (&start, "e2e4"))
+                .unwrap()
+                .extract()
+                .unwrap();
+            assert_eq!(score, 40);
+
+            // A Python agent against a Rust agent, with the results in a dict
+            let result = m
+                .call_method1("play_match", (mover, "random", 2, 1))
+                .unwrap();
+            let result = result.downcast::<PyDict>().unwrap();
+            assert_eq!(
+                result
+                    .get_item("white")
+                    .unwrap()
+                    .unwrap()
+                    .extract::<String>()
+                    .unwrap(),
+                "Mover"
+            );
+            assert_eq!(
+                result
+                    .get_item("black")
+                    .unwrap()
+                    .unwrap()
+                    .extract::<String>()
+                    .unwrap(),
+                "RandomAgent"
+            );
+            let total: u32 = ["white_wins", "black_wins", "draws"]
+                .iter()
+                .map(|k| {
+                    result
+                        .get_item(k)
+                        .unwrap()
+                        .unwrap()
+                        .extract::<u32>()
+                        .unwrap()
+                })
+                .sum();
+            assert_eq!(total, 2);
+        });
+    }
+
+    #[test]
+    fn python_errors() {
+        // A Python agent that raises or cheats resigns, and the match raises its error
+        with_module(|m, agents| {
+            let raiser = agents.getattr("Raiser").unwrap().call0().unwrap();
+            let err = m
+                .call_method1("play_match", (raiser, "random", 2, 1))
+                .unwrap_err();
+            Python::with_gil(|py| assert!(err.is_instance_of::<pyo3::exceptions::PyKeyError>(py)));
+
+            let cheater = agents.getattr("Cheater").unwrap().call0().unwrap();
+            let err = m
+                .call_method1("play_match", (cheater, "random", 1, 1))
+                .unwrap_err();
+            assert!(err.to_string().contains("illegal move e2e5"), "{err}");
+        });
+    }
+
+    #[test]
+    fn agent_keeps_the_game() {
+        // The opponent's reply continues the game, and any other position starts a new one
+        let mut agent = PyAgent::new("depth", 2).unwrap();
+        let mut board = Board::default();
+        let m =
+            ChessMove::from_str(&agent.make_move(&board.to_string()).unwrap().unwrap()).unwrap();
+        board = board.make_move_new(m);
+        let reply = MoveGen::new_legal(&board).next().unwrap();
+        board = board.make_move_new(reply);
+        agent.make_move(&board.to_string()).unwrap().unwrap();
+        assert_eq!(agent.game.as_ref().unwrap().actions().len(), 3);
+
+        agent
+            .make_move(&Board::default().to_string())
+            .unwrap()
+            .unwrap();
+        assert_eq!(agent.game.as_ref().unwrap().actions().len(), 1);
+    }
+}
@@ -0,0 +1,220 @@
+use crate::history::{History, Start};
+use crate::{Agent, Turn};
+use chess::{Game, GameResult};
+
+/// Rebuild the game without the last move of each side.
+pub fn take_back(start: chess::Board, game: &Game) -> Game {
+    let moves: Vec<chess::ChessMove> = game
+        .actions()
+        .iter()
+        .filter_map(|a| match a {
+            chess::Action::MakeMove(m) => Some(*m),
+            _ => None,
+        })
+        .collect();
+
+    let mut new_game = Game::new_with_board(start);
+    for m in &moves[..moves.len().saturating_sub(2)] {
+        assert!(new_game.make_move(*m));
+    }
+    new_game
+}
+
+/// Check for fivefold repetition or seventy-five moves without a capture or pawn move, which end the game
+/// even when neither agent claims the draw.
+pub fn is_automatic_draw(history: &History) -> bool {
+    history.halfmove_clock() >= 150 || history.repetitions() >= 5
+}
+
+/// Run the specified number of games with the player agents provided, returning the [white, black, draw] counts.
+pub fn play_game(white: Box<dyn Agent>, black: Box<dyn Agent>, iterations: u32) -> [u32; 3] {
+    run_games(Start::default(), white, black, iterations, false)
+}
+
+/// Run games as `play_game` does, but from the start given, such as a FEN's.
+pub fn play_game_from(
+    start: Start,
+    white: Box<dyn Agent>,
+    black: Box<dyn Agent>,
+    iterations: u32,
+) -> [u32; 3] {
+    run_games(start, white, black, iterations, false)
+}
+
+/// Run games as `play_game` does, printing each move with what the agent's search found, for debugging.
+pub fn play_game_logged(white: Box<dyn Agent>, black: Box<dyn Agent>, iterations: u32) -> [u32; 3] {
+    run_games(Start::default(), white, black, iterations, true)
+}
+
+#[allow(clippy::cast_precision_loss)] // for u32 to f32
+fn run_games(
+    start: Start,
+    mut white: Box<dyn Agent>,
+    mut black: Box<dyn Agent>,
+    iterations: u32,
+    log: bool,
+) -> [u32; 3] {
+    let mut wins = [0, 0, 0];
+    let mut moves: Vec<f32> = Vec::new();
+
+    // Output the agent names so we know who is playing
+    println!("{} vs {}", white.name(), black.name());
+
+    // Play the requested number of games
+    white.set_start(start);
+    black.set_start(start);
+    let started = std::time::Instant::now();
+    for _i in 0..iterations {
+        let mut move_count = 0.0;
+        let mut game = Game::new_with_board(start.board);
+        let mut history = History::from_moves(&start, []).unwrap();
+
+        while game.result().is_none() {
+            let side = game.side_to_move();
+            let turn = if side == chess::Color::White {
+                white.take_turn(&game)
+            } else {
+                black.take_turn(&game)
+            };
+
+            if let (true, Turn::Move(m) | Turn::OfferDraw(m)) = (log, turn) {
+                let agent = if side == chess::Color::White {
+                    &white
+                } else {
+                    &black
+                };
+                match agent.last_info() {
+                    Some(info) => println!("    {side:?} {m}: {info}"),
+                    None => println!("    {side:?} {m}"),
+                }
+            }
+
+            // Asserts are there to ensure a valid move was given and made
+            let board = game.current_position();
+            match turn {
+                Turn::Move(m) => {
+                    assert!(game.make_move(m));
+                    history.push(&board, m, &game.current_position());
+                    move_count += 1.0;
+                }
+                Turn::OfferDraw(m) => {
+                    assert!(game.offer_draw(side));
+                    assert!(game.make_move(m));
+                    history.push(&board, m, &game.current_position());
+                    move_count += 1.0;
+
+                    // The opponent answers the offer before making its own move
+                    let opponent = if side == chess::Color::White {
+                        &mut black
+                    } else {
+                        &mut white
+                    };
+                    if game.result().is_none() && opponent.accept_draw(&game) {
+                        assert!(game.accept_draw());
+                    }
+                }
+                Turn::Resign => assert!(game.resign(side)),
+                // A claim the rules do not allow forfeits the game, as an illegal move would
+                Turn::ClaimDraw => {
+                    if !game.declare_draw() {
+                        assert!(game.resign(side));
+                    }
+                }
+                Turn::TakeBack => {
+                    game = take_back(start.board, &game);
+                    history = History::from_game(&game, Some(&start));
+                    move_count = game.actions().len() as f32;
+                }
+            }
+
+            // No claim is needed for a draw by fivefold repetition or the seventy-five move rule
+            if game.result().is_none() && is_automatic_draw(&history) {
+                game.declare_draw();
+            }
+        }
+        moves.push(move_count);
+
+        match game.result().unwrap() {
+            GameResult::WhiteCheckmates | GameResult::BlackResigns => wins[0] += 1,
+            GameResult::WhiteResigns | GameResult::BlackCheckmates => wins[1] += 1,
+            _ => wins[2] += 1,
+        }
+    }
+
+    // Calculate the averages (use full turns, where white + black = 1 full turn)
+    let elapsed = started.elapsed();
+    let m_avg: f32 = moves.iter().sum::<f32>() / iterations as f32 / 2.0;
+    let d_avg = elapsed / iterations;
+    println!(
+        "  {:?}, avg {:.1} full turns, avg {:.2?}, total {:.2?}",
+        wins, m_avg, d_avg, elapsed
+    );
+    wins
+}
+
+//==============================================================================
+#[cfg(test)]
+mod test {
+    use super::*;
+    use chess::ChessMove;
+    use std::str::FromStr;
+
+    /// Takes the turns in order, starting over when they run out.
+    struct Script(Vec<Turn>, usize);
+    impl Script {
+        fn new(turns: &[&str]) -> Box<Self> {
+            let turns = turns
+                .iter()
+                .map(|t| match *t {
+                    "claim" => Turn::ClaimDraw,
+                    "undo" => Turn::TakeBack,
+                    "resign" => Turn::Resign,
+                    m => Turn::Move(ChessMove::from_str(m).unwrap()),
+                })
+                .collect();
+            Box::new(Script(turns, 0))
+        }
+    }
+    impl Agent for Script {
+        fn make_move(&mut self, _game: &Game) -> Option<ChessMove> {
+            None
+        }
+
+        fn take_turn(&mut self, _game: &Game) -> Turn {
+            self.1 += 1;
+            self.0[(self.1 - 1) % self.0.len()]
+        }
+
+        fn name(&self) -> String {
+            "Script".to_string()
+        }
+    }
+
+    #[test]
+    fn claim_forfeits() {
+        // A draw claimed without a repetition loses the game
+        assert_eq!(
+            play_game(Script::new(&["claim"]), Script::new(&["e7e5"]), 1),
+            [0, 1, 0]
+        );
+    }
+
+    #[test]
+    fn take_back_from_start() {
+        // The moves are replayed from the game's own start, where the rook move is legal
+        let start = Start::from_fen("4k3/8/8/8/8/8/8/R3K3 w - - 0 1").unwrap();
+        let white = Script::new(&["a1a2", "undo", "resign"]);
+        assert_eq!(
+            play_game_from(start, white, Script::new(&["e8d8"]), 1),
+            [0, 1, 0]
+        );
+    }
+
+    #[test]
+    fn automatic_draw() {
+        // Knights shuffling back and forth repeat the position five times, which ends the game without a claim
+        let white = Script::new(&["g1f3", "f3g1"]);
+        let black = Script::new(&["g8f6", "f6g8"]);
+        assert_eq!(play_game(white, black, 1), [0, 0, 1]);
+    }
+}
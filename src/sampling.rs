@@ -0,0 +1,127 @@
+use crate::eval::{Evaluator, NodeState};
+use crate::score::Score;
+use chess::{ChessMove, MoveGen};
+use rand::rngs::StdRng;
+use rand::seq::IteratorRandom;
+use std::fmt;
+
+/// Which children a search node expands.  Sampling a few moves per node lets the tree look deeper in the same time.
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum Sampling {
+    /// Expand every legal move.
+    Full,
+    /// Expand up to N randomly chosen moves (Monte Carlo sampling).
+    Random(usize),
+    /// Expand up to N moves with the best static score for the side to move.
+    Best(usize),
+}
+impl Sampling {
+    /// Score the legal moves from the board's score and state, and keep the sampled ones with their scores and states.
+    pub fn choose(
+        self,
+        board: &chess::Board,
+        movegen: MoveGen,
+        is_white: bool,
+        (score, state): (Score, NodeState),
+        evaluator: &dyn Evaluator,
+        rng: &mut StdRng,
+    ) -> Vec<(ChessMove, Score, NodeState)> {
+        let scored = movegen.map(|m| {
+            let (score, state) = evaluator.score_move_with(board, is_white, m, score, state);
+            (m, score, state)
+        });
+        match self {
+            Sampling::Full => scored.collect(),
+            Sampling::Random(n) => {
+                // choose_multiple keeps movegen order when all moves fit, so results only depend on the seed
+                let mut moves = scored.choose_multiple(rng, n.max(1));
+                moves.sort_by_key(|(m, _, _)| *m);
+                moves
+            }
+            Sampling::Best(n) => {
+                // Scores are from the agent's point of view, so the opponent's best moves score lowest
+                let mut moves: Vec<(ChessMove, Score, NodeState)> = scored.collect();
+                if (board.side_to_move() == chess::Color::White) == is_white {
+                    moves.sort_by_key(|&(_, s, _)| std::cmp::Reverse(s));
+                } else {
+                    moves.sort_by_key(|&(_, s, _)| s);
+                }
+                moves.truncate(n.max(1));
+                moves
+            }
+        }
+    }
+}
+impl fmt::Display for Sampling {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Sampling::Full => write!(f, "full"),
+            Sampling::Random(n) => write!(f, "random {n}"),
+            Sampling::Best(n) => write!(f, "best {n}"),
+        }
+    }
+}
+
+//==============================================================================
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::score::Calc;
+    use chess::{Board, Square};
+    use rand::SeedableRng;
+
+    fn choose(
+        sampling: Sampling,
+        board: &Board,
+        is_white: bool,
+        seed: u64,
+    ) -> Vec<(ChessMove, Score)> {
+        let calc = Calc::new();
+        let (score, state) = (
+            calc.score_board(board, is_white),
+            calc.node_state(board, is_white),
+        );
+        let mut rng = StdRng::seed_from_u64(seed);
+        let moves = sampling.choose(
+            board,
+            MoveGen::new_legal(board),
+            is_white,
+            (score, state),
+            &calc,
+            &mut rng,
+        );
+        moves.into_iter().map(|(m, s, _)| (m, s)).collect()
+    }
+
+    #[test]
+    fn full() {
+        assert_eq!(choose(Sampling::Full, &Board::default(), true, 0).len(), 20);
+    }
+
+    #[test]
+    fn random() {
+        let board = Board::default();
+        let moves = choose(Sampling::Random(5), &board, true, 1);
+        assert_eq!(moves.len(), 5);
+        assert!(moves.iter().all(|(m, _)| board.legal(*m)));
+
+        // The same seed gives the same sample, and a sample larger than the moves keeps them all
+        assert_eq!(moves, choose(Sampling::Random(5), &board, true, 1));
+        assert_eq!(choose(Sampling::Random(50), &board, true, 1).len(), 20);
+        assert_eq!(choose(Sampling::Random(0), &board, true, 1).len(), 1);
+    }
+
+    #[test]
+    fn best() {
+        // White's best opening moves by piece-square score are the knights to the center (+50)
+        let board = Board::default();
+        let moves = choose(Sampling::Best(2), &board, true, 0);
+        assert_eq!(moves.len(), 2);
+        assert!(moves.contains(&(ChessMove::new(Square::B1, Square::C3, None), Score::cp(50))));
+        assert!(moves.contains(&(ChessMove::new(Square::G1, Square::F3, None), Score::cp(50))));
+
+        // Scoring for black, white's best moves are the lowest scores
+        let moves = choose(Sampling::Best(2), &board, false, 0);
+        assert!(moves.iter().all(|(_, s)| *s == Score::cp(-50)));
+    }
+}
@@ -0,0 +1,865 @@
+use crate::eval::Evaluator;
+use crate::history::{History, Start};
+use crate::info::{InfoCallback, SearchInfo};
+use crate::sampling::Sampling;
+use crate::score::{Calc, Score};
+use crate::threshold::Thresholds;
+use crate::tree::{Tree, TreeSize, DEFAULT_NODE_BUDGET};
+use crate::Turn;
+use chess::MoveGen;
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use rayon::prelude::*;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::Instant;
+
+/// Nodes with at least this many plies left below them are expanded in parallel.  Shallower subtrees are too small to
+/// be worth a task.
+const PARALLEL_DEPTH: u8 = 2;
+
+/// How a tree search spreads its work.
+pub enum Strategy {
+    /// Build the tree on the calling thread.
+    Sequential,
+    /// Build the tree on a work-stealing pool, splitting subtrees below the root between its threads.
+    Pool(rayon::ThreadPool),
+}
+impl Strategy {
+    /// A pool of `threads` threads.
+    pub fn pool(threads: usize) -> Self {
+        Strategy::Pool(
+            rayon::ThreadPoolBuilder::new()
+                .num_threads(threads.max(1))
+                .build()
+                .unwrap(),
+        )
+    }
+
+    fn threads(&self) -> usize {
+        match self {
+            Strategy::Sequential => 1,
+            Strategy::Pool(pool) => pool.current_num_threads(),
+        }
+    }
+}
+
+/// The tree search behind `DepthAgent` and `ThreadAgent`: look a number of moves ahead, averaging the scores of each
+/// move's replies, and keep the tree between moves.  Supports Monte Carlo sampling.
+pub struct TreeSearch {
+    evaluator: Box<dyn Evaluator>,
+    tree: Option<Tree>,
+    node_budget: usize,
+    root: Option<chess::Board>,
+    depth: u8,
+    thresholds: Thresholds,
+    contempt: Score,
+    last_score: Option<Score>,
+    sampling: Sampling,
+    rng: StdRng,
+    strategy: Strategy,
+    nodes: Vec<u64>,
+    info: Option<SearchInfo>,
+    callback: Option<InfoCallback>,
+    stop: Arc<AtomicBool>,
+    /// Whether the stop flag cut the last search short.
+    stopped: bool,
+    start: Option<Start>,
+}
+impl TreeSearch {
+    pub fn new(depth: u8, strategy: Strategy) -> Self {
+        TreeSearch {
+            evaluator: Box::new(Calc::new()),
+            tree: None,
+            node_budget: DEFAULT_NODE_BUDGET,
+            root: None,
+            depth,
+            thresholds: Thresholds::default(),
+            contempt: Score::ZERO,
+            last_score: None,
+            sampling: Sampling::Full,
+            rng: StdRng::seed_from_u64(0),
+            strategy,
+            nodes: Vec::new(),
+            info: None,
+            callback: None,
+            stop: Arc::new(AtomicBool::new(false)),
+            stopped: false,
+            start: None,
+        }
+    }
+
+    /// The agent's name, such as "DepthAgent(3)" or "ThreadAgent(3, random 5)".
+    pub fn name(&self, agent: &str) -> String {
+        if self.sampling == Sampling::Full {
+            format!("{agent}({})", self.depth)
+        } else {
+            format!("{agent}({}, {})", self.depth, self.sampling)
+        }
+    }
+
+    /// Set the scores at which to resign and to offer, accept or claim draws.
+    pub fn set_thresholds(&mut self, thresholds: Thresholds) {
+        self.thresholds = thresholds;
+    }
+
+    /// How much the agent dislikes a draw by repetition or the fifty-move rule.  A draw scores `-contempt`, so a
+    /// positive contempt plays on in even positions and a negative one steers for draws.
+    pub fn set_contempt(&mut self, contempt: Score) {
+        self.contempt = contempt;
+    }
+
+    /// Expand only a sample of the moves at each node, with a seed for repeatable random samples.
+    pub fn set_sampling(&mut self, sampling: Sampling, seed: u64) {
+        self.sampling = sampling;
+        self.rng = StdRng::seed_from_u64(seed);
+    }
+
+    /// Keep at most this many nodes between moves, pruning the least promising subtrees after each search.
+    pub fn set_node_budget(&mut self, nodes: usize) {
+        self.node_budget = nodes;
+    }
+
+    /// Score positions with another evaluator than the default `Calc`.  The tree kept so far was scored by the old one,
+    /// so it is dropped.
+    pub fn set_evaluator(&mut self, evaluator: Box<dyn Evaluator>) {
+        self.evaluator = evaluator;
+        self.tree = None;
+        self.root = None;
+    }
+
+    /// The size of the tree kept from the last search.
+    pub fn tree_size(&self) -> TreeSize {
+        self.tree
+            .as_ref()
+            .map_or(TreeSize { nodes: 0, bytes: 0 }, Tree::size)
+    }
+
+    /// Nodes visited by each thread during the last search, for checking the work is balanced.
+    pub fn nodes_per_thread(&self) -> &[u64] {
+        &self.nodes
+    }
+
+    /// Report each search to the callback.  The tree is built in one pass, so there is one report per search.
+    pub fn set_info_callback(&mut self, callback: InfoCallback) {
+        self.callback = Some(callback);
+    }
+
+    /// Stop building the tree when the flag is set.  The scores of a partly built tree mix depths, so its move may be
+    /// poor.
+    pub fn set_stop_flag(&mut self, stop: Arc<AtomicBool>) {
+        self.stop = stop;
+    }
+
+    /// Whether the stop flag cut the last search short, leaving part of the tree unbuilt.
+    pub fn stopped(&self) -> bool {
+        self.stopped
+    }
+
+    /// Build the tree this deep from now on, extending the tree kept from a shallower search.
+    pub fn set_depth(&mut self, depth: u8) {
+        self.depth = depth;
+    }
+
+    /// Where the games searched from now on began, for finding repetitions before the moves the tree knows.
+    pub fn set_start(&mut self, start: Start) {
+        self.start = Some(start);
+    }
+
+    /// What the last search found.  The hash is full when the tree reaches the node budget.
+    pub fn last_info(&self) -> Option<SearchInfo> {
+        self.info.clone()
+    }
+
+    /// Decide what to do on this turn from the best move and its score.
+    pub fn take_turn(&mut self, game: &chess::Game) -> Turn {
+        let best = self.search(game);
+        self.thresholds.turn(game, best)
+    }
+
+    /// Accept a draw if the last search scored at or below the draw threshold.
+    pub fn accept_draw(&self) -> bool {
+        self.thresholds.accept(self.last_score)
+    }
+
+    /// Search for the best move, returning it with its score.
+    pub fn search(&mut self, game: &chess::Game) -> Option<(chess::ChessMove, Score)> {
+        self.analyze(game, 1)
+            .first()
+            .map(|info| (info.pv[0], info.score))
+    }
+
+    /// Search for the best `lines` moves, best first, each with its propagated score and principal variation.
+    pub fn analyze(&mut self, game: &chess::Game, lines: usize) -> Vec<SearchInfo> {
+        // Prune the tree by going two levels down (our move, opponent move)
+        // If first move of game or not found (due to sampling), create new root
+        let start = Instant::now();
+        self.reroot(game);
+
+        // Build the tree to the proper depth, counting nodes for each thread
+        let nodes: Vec<AtomicU64> = (0..self.strategy.threads())
+            .map(|_| AtomicU64::new(0))
+            .collect();
+        let build = Build {
+            is_white: game.side_to_move() == chess::Color::White,
+            evaluator: &*self.evaluator,
+            sampling: self.sampling,
+            parallel: matches!(self.strategy, Strategy::Pool(_)),
+            nodes: &nodes,
+            draw_score: -self.contempt,
+            depth: self.depth,
+            stop: &self.stop,
+            cut: AtomicBool::new(false),
+        };
+        let (tree, rng, board) = (
+            self.tree.as_mut().unwrap(),
+            &mut self.rng,
+            game.current_position(),
+        );
+        let history = &mut History::from_game(game, self.start.as_ref());
+        match &self.strategy {
+            Strategy::Sequential => build.tree(tree, 0, &board, self.depth, rng, history),
+            Strategy::Pool(pool) => {
+                pool.install(|| build.tree(tree, 0, &board, self.depth, rng, history))
+            }
+        }
+        self.stopped = build.cut.into_inner();
+        self.nodes = nodes.into_iter().map(AtomicU64::into_inner).collect();
+
+        // Rank the root moves, the first of equal scores ahead, and follow the line each side is expected to play
+        // after them, before pruning can cut it short
+        let tree = self.tree.as_mut().unwrap();
+        let mut ranked: Vec<usize> = tree.children(0).collect();
+        ranked.sort_by_key(|&x| std::cmp::Reverse(tree[x].propagate_score));
+        let hashfull = (tree.len() * 1000 / self.node_budget.max(1)).min(1000) as u16;
+        let nodes = self.nodes.iter().sum();
+        let infos: Vec<SearchInfo> = ranked
+            .into_iter()
+            .take(lines.max(1))
+            .map(|x| {
+                let mut pv = vec![tree[x].the_move.unwrap()];
+                pv.extend(tree.principal_variation(x, false));
+                SearchInfo::new(
+                    self.depth,
+                    tree[x].propagate_score,
+                    nodes,
+                    start.elapsed(),
+                    hashfull,
+                    pv,
+                )
+            })
+            .collect();
+
+        self.info = infos.first().cloned();
+        if let (Some(info), Some(callback)) = (&self.info, &mut self.callback) {
+            callback(info);
+        }
+        if tree.len() > self.node_budget {
+            tree.prune(self.node_budget);
+        }
+        self.last_score = self.info.as_ref().map(|i| i.score);
+        infos
+    }
+
+    fn reroot(&mut self, game: &chess::Game) {
+        // Keep the whole tree for the same position, as after pondering on it
+        if self.root == Some(game.current_position()) && self.tree.is_some() {
+            return;
+        }
+        let mut new_root = None;
+
+        // Get the last two moves and attempt to find the node (may not exist if new or sampling)
+        let mut moves = game.actions().iter().rev().filter_map(|a| match a {
+            chess::Action::MakeMove(m) => Some(*m),
+            _ => None,
+        });
+        let move2 = moves.next();
+        let move1 = moves.next();
+
+        // Only reuse the tree if it was built for the position two moves ago (not after a take back)
+        let continues = match (self.root, move1, move2) {
+            (Some(root), Some(m1), Some(m2)) if root.legal(m1) => {
+                let board = root.make_move_new(m1);
+                board.legal(m2) && board.make_move_new(m2) == game.current_position()
+            }
+            _ => false,
+        };
+
+        // Copy out the subtree so the rest of the old tree is freed
+        if let (true, Some(tree), Some(m1), Some(m2)) = (continues, &self.tree, move1, move2) {
+            new_root = tree
+                .child(0, m1)
+                .and_then(|x| tree.child(x, m2))
+                .map(|y| tree.subtree(y));
+        }
+
+        self.tree = new_root;
+
+        if self.tree.is_none() {
+            let (board, is_white) = (
+                game.current_position(),
+                game.side_to_move() == chess::Color::White,
+            );
+            let (score, state) = (
+                self.evaluator.score_board(&board, is_white),
+                self.evaluator.node_state(&board, is_white),
+            );
+            self.tree = Some(Tree::new(score, state));
+        }
+        self.root = Some(game.current_position());
+    }
+}
+
+/// What stays the same while building one tree.
+struct Build<'a> {
+    is_white: bool,
+    evaluator: &'a dyn Evaluator,
+    sampling: Sampling,
+    parallel: bool,
+    nodes: &'a [AtomicU64],
+    /// Score of a repeated position or one reached after fifty moves without a capture or pawn move.
+    draw_score: Score,
+    /// Plies searched below the root, for scoring nearer mates higher.
+    depth: u8,
+    stop: &'a AtomicBool,
+    /// Set when a node is left unbuilt because of the stop flag.
+    cut: AtomicBool,
+}
+impl Build<'_> {
+    /// Build out the move tree to the specified depth, progating scores up
+    /// `history` holds the game and the moves down to this node.
+    fn tree(
+        &self,
+        tree: &mut Tree,
+        node: usize,
+        board: &chess::Board,
+        depth: u8,
+        rng: &mut StdRng,
+        history: &mut History,
+    ) {
+        if self.stop.load(Ordering::Relaxed) {
+            self.cut.store(true, Ordering::Relaxed);
+            return;
+        }
+        let thread = rayon::current_thread_index().unwrap_or(0) % self.nodes.len();
+        self.nodes[thread].fetch_add(1, Ordering::Relaxed);
+
+        let movegen = MoveGen::new_legal(board);
+        let is_white = self.is_white;
+        let is_board_white = board.side_to_move() == chess::Color::White;
+
+        // If no more moves, check the status and stop
+        // Avoid calling board.status directly because it does a MoveGen, so we might as well do it and reuse it.
+        if movegen.len() == 0 {
+            if *board.checkers() == chess::EMPTY {
+                // Stalemate, keep score
+                tree[node].propagate_score = tree[node].score;
+            } else if (is_white && is_board_white) || (!is_white && !is_board_white) {
+                // white lost and is scoring
+                tree[node].propagate_score = Score::mated_in(self.depth - depth);
+            } else if (!is_white && is_board_white) || (is_white && !is_board_white) {
+                // White lost, black scoring, black lost, white scoring
+                tree[node].propagate_score = Score::mate_in(self.depth - depth);
+            }
+        }
+        // if level one exists, fill the next level
+        else if tree.children(node).is_empty() && depth > 0 {
+            let scored = (tree[node].score, tree[node].state);
+            let moves = self
+                .sampling
+                .choose(board, movegen, is_white, scored, self.evaluator, rng);
+            tree.add_children(node, moves);
+            self.children(tree, node, board, depth, rng, history);
+        } else if depth > 0 {
+            // The next level existed, so just build out any following if needed, and score draws again in case the
+            // contempt changed
+            self.children(tree, node, board, depth, rng, history);
+        }
+    }
+
+    /// Build the subtree of each child and average their scores.  In parallel, deep enough children are tasks that
+    /// idle threads steal, so the work splits below the root when there are few root moves.  Each task builds a copy
+    /// of its subtree that is grafted back into the tree.  Children that draw by repetition or the fifty-move rule are
+    /// scored as draws and not searched.
+    fn children(
+        &self,
+        tree: &mut Tree,
+        node: usize,
+        board: &chess::Board,
+        depth: u8,
+        rng: &mut StdRng,
+        history: &mut History,
+    ) {
+        if self.parallel && depth >= PARALLEL_DEPTH {
+            // Each task samples with its own generator, seeded in move order so a game can be repeated
+            let jobs: Vec<(usize, u64)> = tree.children(node).map(|c| (c, rng.gen())).collect();
+            let shared = &*tree;
+            let subtrees: Vec<Tree> = jobs
+                .par_iter()
+                .map(|&(c, seed)| {
+                    let mut sub = shared.subtree(c);
+                    let new_board = board.make_move_new(sub[0].the_move.unwrap());
+                    let mut history = history.clone();
+                    history.push(board, sub[0].the_move.unwrap(), &new_board);
+                    if history.is_draw() {
+                        sub[0].propagate_score = self.draw_score;
+                    } else {
+                        self.tree(
+                            &mut sub,
+                            0,
+                            &new_board,
+                            depth - 1,
+                            &mut StdRng::seed_from_u64(seed),
+                            &mut history,
+                        );
+                    }
+                    sub
+                })
+                .collect();
+            for ((c, _), sub) in jobs.into_iter().zip(subtrees) {
+                tree.graft(c, sub);
+            }
+        } else {
+            // Reuse this board in the loop to avoid reallocating
+            let mut new_board = chess::Board::default();
+            for c in tree.children(node) {
+                board.make_move(tree[c].the_move.unwrap(), &mut new_board);
+                history.push(board, tree[c].the_move.unwrap(), &new_board);
+                if history.is_draw() {
+                    tree[c].propagate_score = self.draw_score;
+                } else {
+                    self.tree(tree, c, &new_board, depth - 1, rng, history);
+                }
+                history.pop();
+            }
+        }
+        tree[node].propagate_score = tree.average(node);
+    }
+}
+
+/// The `Agent` impl and the settings shared by the agents that wrap a `TreeSearch` in a `search` field, which differ
+/// only in their strategy.
+macro_rules! tree_search_agent {
+    ($agent:ident) => {
+        impl $crate::Agent for $agent {
+            fn make_move(&mut self, game: &chess::Game) -> Option<chess::ChessMove> {
+                self.search.search(game).map(|(m, _)| m)
+            }
+
+            fn take_turn(&mut self, game: &chess::Game) -> $crate::Turn {
+                self.search.take_turn(game)
+            }
+
+            fn accept_draw(&mut self, _game: &chess::Game) -> bool {
+                self.search.accept_draw()
+            }
+
+            fn name(&self) -> String {
+                self.search.name(stringify!($agent))
+            }
+
+            fn set_info_callback(&mut self, callback: $crate::InfoCallback) {
+                self.search.set_info_callback(callback);
+            }
+
+            fn last_info(&self) -> Option<$crate::SearchInfo> {
+                self.search.last_info()
+            }
+
+            fn analyze(&mut self, game: &chess::Game, lines: usize) -> Vec<$crate::SearchInfo> {
+                self.search.analyze(game, lines)
+            }
+
+            fn set_stop_flag(&mut self, stop: std::sync::Arc<std::sync::atomic::AtomicBool>) {
+                self.search.set_stop_flag(stop);
+            }
+
+            fn stopped(&self) -> bool {
+                self.search.stopped()
+            }
+
+            fn set_depth(&mut self, depth: u8) {
+                self.search.set_depth(depth);
+            }
+
+            fn set_start(&mut self, start: $crate::Start) {
+                self.search.set_start(start);
+            }
+        }
+        impl $agent {
+            /// Set the scores at which to resign and to offer, accept or claim draws.
+            pub fn set_thresholds(&mut self, thresholds: $crate::threshold::Thresholds) {
+                self.search.set_thresholds(thresholds);
+            }
+
+            /// How much the agent dislikes a draw by repetition or the fifty-move rule, scoring one as `-contempt`.
+            pub fn set_contempt(&mut self, contempt: $crate::Score) {
+                self.search.set_contempt(contempt);
+            }
+
+            /// Expand only a sample of the moves at each node, with a seed for repeatable random samples.
+            pub fn set_sampling(&mut self, sampling: $crate::Sampling, seed: u64) {
+                self.search.set_sampling(sampling, seed);
+            }
+
+            /// Keep at most this many nodes between moves, pruning the least promising subtrees after each search.
+            pub fn set_node_budget(&mut self, nodes: usize) {
+                self.search.set_node_budget(nodes);
+            }
+
+            /// Score positions with another evaluator than the default `Calc`, such as `Material`.
+            pub fn set_evaluator(&mut self, evaluator: Box<dyn $crate::Evaluator>) {
+                self.search.set_evaluator(evaluator);
+            }
+
+            /// The size of the tree kept from the last search.
+            pub fn tree_size(&self) -> $crate::tree::TreeSize {
+                self.search.tree_size()
+            }
+        }
+    };
+}
+pub(crate) use tree_search_agent;
+
+//==============================================================================
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::eval::{Material, NodeState};
+    use chess::{Board, ChessMove, Game};
+    use more_asserts as ma;
+    use std::str::FromStr;
+    use std::sync::{Arc, Mutex};
+
+    /// Run a test against every strategy
+    fn each_strategy(test: impl Fn(Strategy)) {
+        test(Strategy::Sequential);
+        test(Strategy::pool(4));
+    }
+
+    /// Build a tree from the starting position
+    fn build(strategy: &Strategy, tree: &mut Tree, depth: u8, sampling: Sampling) {
+        let nodes = [AtomicU64::new(0)];
+        let build = Build {
+            is_white: true,
+            evaluator: &Calc::new(),
+            sampling,
+            parallel: matches!(strategy, Strategy::Pool(_)),
+            nodes: &nodes,
+            draw_score: Score::ZERO,
+            depth,
+            stop: &AtomicBool::new(false),
+            cut: AtomicBool::new(false),
+        };
+        let board = Board::default();
+        build.tree(
+            tree,
+            0,
+            &board,
+            depth,
+            &mut StdRng::seed_from_u64(0),
+            &mut History::new(&board),
+        );
+    }
+
+    #[test]
+    fn make_two_moves() {
+        each_strategy(|strategy| {
+            let mut game = Game::new();
+            let calc = Calc::new();
+
+            let mut white = TreeSearch::new(1, strategy);
+            let mut black = TreeSearch::new(1, Strategy::Sequential);
+
+            // Ensure a valid white and black move are made, and that their scores improve
+            let mut s = calc.score_board(&game.current_position(), true);
+            assert!(game.make_move(white.search(&game).unwrap().0));
+            ma::assert_le!(s, calc.score_board(&game.current_position(), true));
+
+            s = calc.score_board(&game.current_position(), false);
+            assert!(game.make_move(black.search(&game).unwrap().0));
+            ma::assert_le!(s, calc.score_board(&game.current_position(), false));
+        });
+    }
+
+    /// Test resigning and offering draws from the best score
+    #[test]
+    fn thresholds() {
+        each_strategy(|strategy| {
+            let game = Game::new();
+            let mut search = TreeSearch::new(1, strategy);
+            assert!(matches!(search.take_turn(&game), Turn::Move(_)));
+            assert!(!search.accept_draw());
+
+            // Every score is below the resign threshold
+            search.set_thresholds(Thresholds {
+                resign: Some(Score::INFINITY),
+                draw: None,
+            });
+            assert_eq!(search.take_turn(&game), Turn::Resign);
+
+            // The opening is even, so offer and accept draws
+            search.set_thresholds(Thresholds {
+                resign: None,
+                draw: Some(Score::cp(100)),
+            });
+            assert!(matches!(search.take_turn(&game), Turn::OfferDraw(_)));
+            assert!(search.accept_draw());
+        });
+    }
+
+    /// Test scoring repetitions by the contempt for draws
+    #[test]
+    fn contempt() {
+        each_strategy(|strategy| {
+            // Black can repeat the starting position by moving the knight back
+            let mut game = Game::new();
+            for m in ["g1f3", "g8f6", "f3g1"] {
+                assert!(game.make_move(ChessMove::from_str(m).unwrap()));
+            }
+            let back = ChessMove::from_str("f6g8").unwrap();
+
+            // Liking draws, black repeats, and disliking them it plays on
+            let mut search = TreeSearch::new(1, strategy);
+            search.set_contempt(Score::cp(-500));
+            assert_eq!(search.search(&game), Some((back, Score::cp(500))));
+            search.set_contempt(Score::cp(500));
+            assert_ne!(search.search(&game).unwrap().0, back);
+        });
+    }
+
+    /// Test telling a search the stop flag cut short from one that finished
+    #[test]
+    fn stopped() {
+        each_strategy(|strategy| {
+            let game = Game::new();
+            let mut search = TreeSearch::new(2, strategy);
+            search.search(&game);
+            assert!(!search.stopped());
+            search.set_stop_flag(Arc::new(AtomicBool::new(true)));
+            search.set_depth(3);
+            search.search(&game);
+            assert!(search.stopped());
+        });
+    }
+
+    /// Test scoring a mate by its distance, so a deeper search does not put it off
+    #[test]
+    fn mate_distance() {
+        each_strategy(|strategy| {
+            let game = Game::new_with_board(
+                Board::from_str("6k1/5ppp/8/8/8/8/5PPP/4R1K1 w - - 0 1").unwrap(),
+            );
+            let mut search = TreeSearch::new(3, strategy);
+            let mate = ChessMove::from_str("e1e8").unwrap();
+            assert_eq!(search.search(&game), Some((mate, Score::mate_in(1))));
+        });
+    }
+
+    /// Test plugging in another evaluator, which drops the tree the old one scored
+    #[test]
+    fn evaluator() {
+        each_strategy(|strategy| {
+            // By material alone, taking the knight is best even though the rook can take the pawn back
+            let game = Game::new_with_board(
+                Board::from_str("3rk3/8/8/3n4/4P3/8/8/4K3 w - - 0 1").unwrap(),
+            );
+            let mut search = TreeSearch::new(2, strategy);
+            search.search(&game).unwrap();
+            search.set_evaluator(Box::new(Material));
+            assert_eq!(search.tree_size().nodes, 0);
+            assert_eq!(
+                search.search(&game).unwrap().0,
+                ChessMove::from_str("e4d5").unwrap()
+            );
+        });
+    }
+
+    /// Scores a board by the plies from the root, which only the state carried down the tree knows.
+    struct Plies;
+    impl Evaluator for Plies {
+        fn score_board(&self, _board: &Board, _is_white: bool) -> Score {
+            Score::ZERO
+        }
+
+        fn score_move_with(
+            &self,
+            _board: &Board,
+            _is_white: bool,
+            _the_move: ChessMove,
+            _score: Score,
+            state: NodeState,
+        ) -> (Score, NodeState) {
+            (Score::cp(state.0 + 1), NodeState(state.0 + 1))
+        }
+    }
+
+    /// Test the evaluator's state is handed down from each node to its children
+    #[test]
+    fn node_state() {
+        each_strategy(|strategy| {
+            let mut search = TreeSearch::new(3, strategy);
+            search.set_evaluator(Box::new(Plies));
+            assert_eq!(search.search(&Game::new()).unwrap().1, Score::cp(3));
+        });
+    }
+
+    /// Test reporting the search with its expected line
+    #[test]
+    fn info() {
+        each_strategy(|strategy| {
+            let reports = Arc::new(Mutex::new(Vec::new()));
+            let mut search = TreeSearch::new(2, strategy);
+            let seen = reports.clone();
+            search.set_info_callback(Box::new(move |info| {
+                seen.lock().unwrap().push(info.clone())
+            }));
+            let (m, score) = search.search(&Game::new()).unwrap();
+
+            // One report, with a move and reply, covering the whole tree
+            let info = search.last_info().unwrap();
+            assert_eq!(*reports.lock().unwrap(), std::slice::from_ref(&info));
+            assert_eq!((info.depth, info.score, info.nodes), (2, score, 421));
+            assert_eq!(info.pv.len(), 2);
+            assert_eq!(info.pv[0], m);
+            assert_eq!(info.hashfull, 0);
+        });
+    }
+
+    /// Test ranking the root moves for analysis
+    #[test]
+    fn analyze() {
+        each_strategy(|strategy| {
+            let game = Game::new();
+            let mut search = TreeSearch::new(2, strategy);
+            let lines = search.analyze(&game, 3);
+            assert_eq!(lines.len(), 3);
+            assert!(lines.windows(2).all(|w| w[0].score >= w[1].score));
+            assert!(lines.iter().all(|l| l.pv.len() == 2));
+            assert_eq!(search.last_info().as_ref(), lines.first());
+
+            // The same best move as a plain search, and no more lines than moves
+            let best = search.search(&game).unwrap();
+            assert_eq!((lines[0].pv[0], lines[0].score), best);
+            assert_eq!(search.analyze(&game, 50).len(), 20);
+        });
+    }
+
+    /// Test counting the nodes each thread visits
+    #[test]
+    fn nodes_per_thread() {
+        each_strategy(|strategy| {
+            let threads = strategy.threads();
+            let game = Game::new();
+            let mut search = TreeSearch::new(2, strategy);
+            assert!(game
+                .current_position()
+                .legal(search.search(&game).unwrap().0));
+            assert_eq!(search.nodes_per_thread().len(), threads);
+
+            // The root, its 20 moves and their 400 replies
+            assert_eq!(search.nodes_per_thread().iter().sum::<u64>(), 421);
+        });
+    }
+
+    /// Test keeping the tree between moves within the node budget
+    #[test]
+    fn node_budget() {
+        each_strategy(|strategy| {
+            let mut game = Game::new();
+            let mut white = TreeSearch::new(3, strategy);
+            let mut black = TreeSearch::new(1, Strategy::Sequential);
+
+            // The root, 20 moves, 400 replies and 8902 positions after those
+            assert!(game.make_move(white.search(&game).unwrap().0));
+            assert_eq!(white.tree_size().nodes, 9323);
+            ma::assert_ge!(white.tree_size().bytes, 9323 * 16);
+            assert!(game.make_move(black.search(&game).unwrap().0));
+
+            // The next search starts from the reply's subtree and is then pruned to the budget
+            white.set_node_budget(1000);
+            assert!(game.make_move(white.search(&game).unwrap().0));
+            ma::assert_le!(white.tree_size().nodes, 1000);
+            ma::assert_gt!(white.tree_size().nodes, 20);
+        });
+    }
+
+    /// Test building a tree to depths 1 to 4
+    #[test]
+    fn build_tree() {
+        each_strategy(|strategy| {
+            // Starting board has 20 opening moves, and the average move is 6 at odd depths and 0 at even ones
+            for (depth, score) in [(1, 6), (2, 0), (3, 6), (4, 0)] {
+                let mut tree = Tree::new(Score::ZERO, NodeState::default());
+                build(&strategy, &mut tree, depth, Sampling::Full);
+                assert_eq!(tree.children(0).len(), 20);
+                assert_eq!(tree[0].score, Score::ZERO);
+                assert_eq!(tree[0].propagate_score, Score::cp(score));
+            }
+        });
+    }
+
+    /// Test building level two tree from a level 1
+    #[test]
+    fn build_tree_2_from_1() {
+        each_strategy(|strategy| {
+            let mut tree = Tree::new(Score::ZERO, NodeState::default());
+            build(&strategy, &mut tree, 1, Sampling::Full);
+            assert_eq!(tree.children(0).len(), 20);
+            assert_eq!(tree[0].score, Score::ZERO);
+            assert_eq!(tree[0].propagate_score, Score::cp(6));
+
+            build(&strategy, &mut tree, 2, Sampling::Full);
+            assert_eq!(tree.children(0).len(), 20);
+            assert_eq!(tree[0].score, Score::ZERO);
+            assert_eq!(tree[0].propagate_score, Score::ZERO);
+        });
+    }
+
+    /// Test expanding a sample of the moves at each node
+    #[test]
+    fn build_tree_sampled() {
+        each_strategy(|strategy| {
+            let mut tree = Tree::new(Score::ZERO, NodeState::default());
+            build(&strategy, &mut tree, 3, Sampling::Random(3));
+            assert_eq!(tree.children(0).len(), 3);
+            assert!(tree.children(0).all(|n| tree.children(n).len() == 3));
+        });
+    }
+
+    /// Test sampled searches play legal games, rebuilding when the opponent plays a move that was not sampled
+    #[test]
+    fn sampled_game() {
+        each_strategy(|strategy| {
+            let threads = strategy.threads();
+            let play = |seed, strategy| {
+                let mut game = Game::new();
+                let mut white = TreeSearch::new(3, strategy);
+                let mut black = TreeSearch::new(2, Strategy::Sequential);
+                white.set_sampling(Sampling::Random(4), seed);
+                black.set_sampling(Sampling::Best(3), seed);
+                assert_eq!(white.name("DepthAgent"), "DepthAgent(3, random 4)");
+
+                let mut moves = Vec::new();
+                for _ in 0..6 {
+                    let m = white.search(&game).unwrap().0;
+                    assert!(game.make_move(m));
+                    moves.push(m);
+                    let m = black.search(&game).unwrap().0;
+                    assert!(game.make_move(m));
+                    moves.push(m);
+                }
+                moves
+            };
+
+            // The same seed plays the same game
+            let first = play(7, strategy);
+            let again = if threads == 1 {
+                Strategy::Sequential
+            } else {
+                Strategy::pool(threads)
+            };
+            assert_eq!(first, play(7, again));
+        });
+    }
+}
@@ -0,0 +1,175 @@
+use crate::score::Score;
+use chess::{BitBoard, Board, ChessMove, Color, Piece, Square};
+
+/// Material values for exchanges, in centipawns: pawn, knight, bishop, rook, queen and king.  The piece-square tables
+/// score only where pieces stand, so exchanges need values of their own.
+pub const PIECE_VALUES: [i32; 6] = [100, 320, 330, 500, 900, 20_000];
+
+fn value(piece: Piece) -> i32 {
+    PIECE_VALUES[piece.to_index()]
+}
+
+/// Static exchange evaluation: the material the side to move wins by the capture, when both sides keep capturing on
+/// its square with their least valuable piece for as long as that pays.  Sliders behind a capturing piece join in
+/// once it has moved (x-rays).  Pins and checks are ignored.  A move that captures nothing scores zero unless the
+/// piece can then be taken.
+pub fn see(board: &Board, m: ChessMove) -> Score {
+    let (from, target) = (m.get_source(), m.get_dest());
+    let Some(mut on_square) = board.piece_on(from).map(value) else {
+        return Score::ZERO;
+    };
+    let mut occupied = *board.combined() ^ BitBoard::from_square(from);
+
+    // What each capture takes, starting with the move itself
+    let mut gains = vec![board.piece_on(target).map_or(0, value)];
+    if board.piece_on(from) == Some(Piece::Pawn)
+        && from.get_file() != target.get_file()
+        && gains[0] == 0
+    {
+        // En passant takes a pawn beside the target square
+        gains[0] = value(Piece::Pawn);
+        occupied ^= BitBoard::from_square(Square::make_square(from.get_rank(), target.get_file()));
+    }
+    if let Some(promotion) = m.get_promotion() {
+        gains[0] += value(promotion) - value(Piece::Pawn);
+        on_square = value(promotion);
+    }
+
+    let mut side = !board.side_to_move();
+    while let Some((square, piece)) = least_attacker(board, target, occupied, side) {
+        // A king cannot take into an attack
+        if piece == Piece::King
+            && attackers(
+                board,
+                target,
+                occupied ^ BitBoard::from_square(square),
+                !side,
+            )
+        {
+            break;
+        }
+        gains.push(on_square);
+        on_square = value(piece);
+        occupied ^= BitBoard::from_square(square);
+        side = !side;
+    }
+
+    // Each side may stop capturing instead, except with the move itself
+    let rest = gains[1..]
+        .iter()
+        .rev()
+        .fold(0, |later, gain| (gain - later).max(0));
+    Score::cp(gains[0] - rest)
+}
+
+/// The side's least valuable piece attacking the square, among the pieces still on the board.
+fn least_attacker(
+    board: &Board,
+    target: Square,
+    occupied: BitBoard,
+    side: Color,
+) -> Option<(Square, Piece)> {
+    let ours = board.color_combined(side) & occupied;
+    let diagonal = chess::get_bishop_moves(target, occupied);
+    let straight = chess::get_rook_moves(target, occupied);
+    let queens = board.pieces(Piece::Queen);
+    let candidates = [
+        (
+            Piece::Pawn,
+            chess::get_pawn_attacks(target, !side, *board.pieces(Piece::Pawn)),
+        ),
+        (
+            Piece::Knight,
+            chess::get_knight_moves(target) & board.pieces(Piece::Knight),
+        ),
+        (Piece::Bishop, diagonal & board.pieces(Piece::Bishop)),
+        (Piece::Rook, straight & board.pieces(Piece::Rook)),
+        (Piece::Queen, (diagonal | straight) & queens),
+        (
+            Piece::King,
+            chess::get_king_moves(target) & board.pieces(Piece::King),
+        ),
+    ];
+    candidates
+        .into_iter()
+        .map(|(piece, attacking)| (piece, attacking & ours))
+        .find(|(_, attacking)| *attacking != chess::EMPTY)
+        .map(|(piece, attacking)| (attacking.to_square(), piece))
+}
+
+/// Whether any of the side's pieces still on the board attack the square.
+fn attackers(board: &Board, target: Square, occupied: BitBoard, side: Color) -> bool {
+    least_attacker(board, target, occupied, side).is_some()
+}
+
+/// The most the side to move can win with one capture, judged by its exchange, or zero when no capture pays: how
+/// much the other side has left hanging.
+pub fn best_capture(board: &Board) -> Score {
+    let mut captures = chess::MoveGen::new_legal(board);
+    captures.set_iterator_mask(*board.color_combined(!board.side_to_move()));
+    captures
+        .map(|m| see(board, m))
+        .fold(Score::ZERO, Score::max)
+}
+
+//==============================================================================
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::str::FromStr;
+
+    fn see_of(fen: &str, m: &str) -> i32 {
+        see(
+            &Board::from_str(fen).unwrap(),
+            ChessMove::from_str(m).unwrap(),
+        )
+        .centipawns()
+    }
+
+    #[test]
+    fn exchanges() {
+        // A knight taken by a pawn that a pawn takes back, and a defended pawn taken by a rook
+        assert_eq!(see_of("4k3/8/2p5/3n4/4P3/8/8/4K3 w - - 0 1", "e4d5"), 220);
+        assert_eq!(see_of("4k3/8/2p5/3p4/8/8/8/3RK3 w - - 0 1", "d1d5"), -400);
+        // An undefended piece, and a quiet move to a square a pawn attacks
+        assert_eq!(see_of("4k3/8/8/3n4/8/8/8/3RK3 w - - 0 1", "d1d5"), 320);
+        assert_eq!(see_of("4k3/8/2p5/8/8/8/8/3RK3 w - - 0 1", "d1d5"), -500);
+        // The queen takes back the pawn that took the rook
+        assert_eq!(see_of("4k3/8/2p5/3p4/8/8/3R4/3QK3 w - - 0 1", "d2d5"), -300);
+    }
+
+    #[test]
+    fn x_rays() {
+        // The rook behind the first one recaptures once it has moved, and the defending rook behind the pawn too
+        assert_eq!(see_of("3rk3/8/8/3p4/8/8/3R4/3RK3 w - - 0 1", "d2d5"), 100);
+        assert_eq!(
+            see_of("3rk3/3r4/8/3p4/8/8/3R4/3RK3 w - - 0 1", "d2d5"),
+            -400
+        );
+        // A bishop behind a queen on the diagonal
+        assert_eq!(
+            see_of("4k3/8/5p2/4p3/3Q4/2B5/8/4K3 w - - 0 1", "d4e5"),
+            100 - 900 + 100
+        );
+    }
+
+    #[test]
+    fn kings_and_special_moves() {
+        // The king cannot take back while another attacker still guards the square
+        assert_eq!(see_of("8/8/4k3/3p4/8/8/3R4/3RK3 w - - 0 1", "d2d5"), 100);
+        assert_eq!(see_of("8/8/4k3/3p4/8/8/8/3RK3 w - - 0 1", "d1d5"), -400);
+        // En passant, and a promotion that cannot be taken
+        assert_eq!(see_of("4k3/8/8/3pP3/8/8/8/4K3 w - d6 0 1", "e5d6"), 100);
+        assert_eq!(see_of("4k3/1P6/8/8/8/8/8/4K3 w - - 0 1", "b7b8q"), 800);
+    }
+
+    #[test]
+    fn hanging_pieces() {
+        // A knight en prise, a defended one, and nothing at the start
+        let board = Board::from_str("4k3/8/2p5/3N4/8/8/8/4K3 b - - 0 1").unwrap();
+        assert_eq!(best_capture(&board), Score::cp(320));
+        let board = Board::from_str("4k3/8/2p5/3N4/4P3/8/8/4K3 b - - 0 1").unwrap();
+        assert_eq!(best_capture(&board), Score::cp(220));
+        assert_eq!(best_capture(&Board::default()), Score::ZERO);
+    }
+}
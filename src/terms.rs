@@ -0,0 +1,397 @@
+use crate::pawns::{in_front, relative_rank};
+use crate::score::Tapered;
+use chess::{BitBoard, Board, Color, File, Piece, Square};
+
+/// The pieces the mobility and attacker values are for, in their order.
+const PIECES: [Piece; 4] = [Piece::Knight, Piece::Bishop, Piece::Rook, Piece::Queen];
+
+/// Per square a knight, bishop, rook or queen can move to, ignoring pins.
+pub const MOBILITY: [Tapered; 4] = [
+    Tapered::new(4, 4),
+    Tapered::new(5, 5),
+    Tapered::new(2, 4),
+    Tapered::new(1, 2),
+];
+/// Per pawn of the side one or two ranks in front of its king, on the king's file or a file beside it.
+pub const PAWN_SHIELD: Tapered = Tapered::new(10, 0);
+/// Per enemy knight, bishop, rook or queen attacking the king or a square next to it.
+pub const KING_ATTACKERS: [Tapered; 4] = [
+    Tapered::new(-10, 0),
+    Tapered::new(-10, 0),
+    Tapered::new(-15, 0),
+    Tapered::new(-25, 0),
+];
+/// Per file at or beside the king with no pawn of the side on it.
+pub const SEMI_OPEN_NEAR_KING: Tapered = Tapered::new(-15, 0);
+/// Per file at or beside the king with no pawns at all, on top of it being semi-open.
+pub const OPEN_NEAR_KING: Tapered = Tapered::new(-10, 0);
+/// Two bishops or more.
+pub const BISHOP_PAIR: Tapered = Tapered::new(30, 50);
+/// A rook on a file without pawns.
+pub const ROOK_OPEN_FILE: Tapered = Tapered::new(25, 10);
+/// A rook on a file with enemy pawns only.
+pub const ROOK_SEMI_OPEN_FILE: Tapered = Tapered::new(10, 5);
+/// A rook on the seventh rank, when the enemy king is on the eighth or enemy pawns are still on the seventh.
+pub const ROOK_SEVENTH: Tapered = Tapered::new(20, 30);
+/// A knight on the fourth to sixth ranks, guarded by a pawn, that no enemy pawn can ever attack.
+pub const KNIGHT_OUTPOST: Tapered = Tapered::new(20, 10);
+
+/// How much each term counts, in percent of its values: 100 as they are, 0 to leave it out, so the strength each
+/// adds can be measured by playing with and without it.
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub struct Weights {
+    pub mobility: i32,
+    /// The pawn shield, attackers near the king and open files next to it.
+    pub king_safety: i32,
+    pub bishop_pair: i32,
+    /// Rooks on open and semi-open files.
+    pub rook_files: i32,
+    pub rook_seventh: i32,
+    pub knight_outposts: i32,
+}
+impl Weights {
+    /// Every term left out, scoring by the piece-square tables (and pawn structure) alone.
+    pub fn none() -> Self {
+        Weights {
+            mobility: 0,
+            king_safety: 0,
+            bishop_pair: 0,
+            rook_files: 0,
+            rook_seventh: 0,
+            knight_outposts: 0,
+        }
+    }
+}
+impl Default for Weights {
+    fn default() -> Self {
+        Weights {
+            mobility: 100,
+            king_safety: 100,
+            bishop_pair: 100,
+            rook_files: 100,
+            rook_seventh: 100,
+            knight_outposts: 100,
+        }
+    }
+}
+
+/// The piece terms of one side, unweighted.
+#[derive(Copy, Clone, Default, PartialEq, Eq, Debug)]
+pub struct PieceTerms {
+    pub mobility: Tapered,
+    pub king_safety: Tapered,
+    pub bishop_pair: Tapered,
+    pub rook_files: Tapered,
+    pub rook_seventh: Tapered,
+    pub knight_outposts: Tapered,
+}
+impl PieceTerms {
+    /// The terms for the side's pieces.
+    pub fn of(board: &Board, side: Color) -> Self {
+        PieceTerms::both(board)[side.to_index()]
+    }
+
+    /// The terms for both sides, by color index.  Each piece's attacks are found once, for its mobility and for the
+    /// enemy king's safety.
+    pub fn both(board: &Board) -> [Self; 2] {
+        let mut terms = [PieceTerms::default(); 2];
+        for side in chess::ALL_COLORS {
+            let ours = board.color_combined(side);
+            let their_king = board.king_square(!side);
+            let zone = chess::get_king_moves(their_king) | BitBoard::from_square(their_king);
+            for (i, piece) in PIECES.into_iter().enumerate() {
+                for square in board.pieces(piece) & ours {
+                    let attacked = attacks(board, piece, square);
+                    terms[side.to_index()].mobility +=
+                        MOBILITY[i] * (attacked & !ours).popcnt() as i32;
+                    if (attacked & zone) != chess::EMPTY {
+                        terms[(!side).to_index()].king_safety += KING_ATTACKERS[i];
+                    }
+                }
+            }
+        }
+        for side in chess::ALL_COLORS {
+            terms[side.to_index()].rest(board, side);
+        }
+        terms
+    }
+
+    /// Add the terms besides mobility and the king's attackers.
+    fn rest(&mut self, board: &Board, side: Color) {
+        let ours = board.color_combined(side);
+        let pawns = board.pieces(Piece::Pawn);
+        let (our_pawns, their_pawns) = (pawns & ours, pawns & board.color_combined(!side));
+
+        self.king_safety += king_shelter(board, side);
+
+        if (board.pieces(Piece::Bishop) & ours).popcnt() >= 2 {
+            self.bishop_pair = BISHOP_PAIR;
+        }
+
+        let their_king = board.king_square(!side);
+        for square in board.pieces(Piece::Rook) & ours {
+            let file = chess::get_file(square.get_file());
+            if (file & pawns) == chess::EMPTY {
+                self.rook_files += ROOK_OPEN_FILE;
+            } else if (file & our_pawns) == chess::EMPTY {
+                self.rook_files += ROOK_SEMI_OPEN_FILE;
+            }
+            let rank = chess::get_rank(square.get_rank());
+            if relative_rank(square, side) == 6
+                && (relative_rank(their_king, side) == 7 || (rank & their_pawns) != chess::EMPTY)
+            {
+                self.rook_seventh += ROOK_SEVENTH;
+            }
+        }
+
+        for square in board.pieces(Piece::Knight) & ours {
+            let guarded = chess::get_pawn_attacks(square, !side, our_pawns) != chess::EMPTY;
+            let attackers =
+                chess::get_adjacent_files(square.get_file()) & in_front(square, side) & their_pawns;
+            if (3..=5).contains(&relative_rank(square, side))
+                && guarded
+                && attackers == chess::EMPTY
+            {
+                self.knight_outposts += KNIGHT_OUTPOST;
+            }
+        }
+    }
+
+    /// Each term in proportion to its weight.
+    pub fn scaled(&self, weights: &Weights) -> Self {
+        let weigh = |term: Tapered, percent: i32| {
+            Tapered::new(
+                term.middlegame * percent / 100,
+                term.endgame * percent / 100,
+            )
+        };
+        PieceTerms {
+            mobility: weigh(self.mobility, weights.mobility),
+            king_safety: weigh(self.king_safety, weights.king_safety),
+            bishop_pair: weigh(self.bishop_pair, weights.bishop_pair),
+            rook_files: weigh(self.rook_files, weights.rook_files),
+            rook_seventh: weigh(self.rook_seventh, weights.rook_seventh),
+            knight_outposts: weigh(self.knight_outposts, weights.knight_outposts),
+        }
+    }
+
+    /// The sum of the terms, each in proportion to its weight.
+    pub fn weighted(&self, weights: &Weights) -> Tapered {
+        self.scaled(weights)
+            .named()
+            .into_iter()
+            .fold(Tapered::default(), |sum, (_, term)| sum + term)
+    }
+
+    /// Each term with its name, for showing them.
+    pub fn named(&self) -> [(&'static str, Tapered); 6] {
+        [
+            ("mobility", self.mobility),
+            ("king safety", self.king_safety),
+            ("bishop pair", self.bishop_pair),
+            ("rook files", self.rook_files),
+            ("rook seventh", self.rook_seventh),
+            ("knight outposts", self.knight_outposts),
+        ]
+    }
+}
+
+/// The weighted piece terms for White less those for Black.
+pub fn piece_terms(board: &Board, weights: &Weights) -> Tapered {
+    if *weights == Weights::none() {
+        return Tapered::default();
+    }
+    let [white, black] = PieceTerms::both(board);
+    white.weighted(weights) - black.weighted(weights)
+}
+
+/// The squares a knight, bishop, rook or queen on the square attacks.
+fn attacks(board: &Board, piece: Piece, square: Square) -> BitBoard {
+    let occupied = *board.combined();
+    match piece {
+        Piece::Knight => chess::get_knight_moves(square),
+        Piece::Bishop => chess::get_bishop_moves(square, occupied),
+        Piece::Rook => chess::get_rook_moves(square, occupied),
+        _ => chess::get_bishop_moves(square, occupied) | chess::get_rook_moves(square, occupied),
+    }
+}
+
+/// The side's pawn shield, less the files open next to its king.
+fn king_shelter(board: &Board, side: Color) -> Tapered {
+    let king = board.king_square(side);
+    let files = chess::get_file(king.get_file()) | chess::get_adjacent_files(king.get_file());
+    let pawns = board.pieces(Piece::Pawn);
+    let our_pawns = pawns & board.color_combined(side);
+    let mut safety = Tapered::default();
+
+    let one = king.forward(side);
+    let two = one.and_then(|square| square.forward(side));
+    let shield = [one, two]
+        .into_iter()
+        .flatten()
+        .fold(chess::EMPTY, |zone, square| {
+            zone | chess::get_rank(square.get_rank())
+        });
+    safety += PAWN_SHIELD * (shield & files & our_pawns).popcnt() as i32;
+
+    let king_file = king.get_file().to_index();
+    for file in king_file.saturating_sub(1)..=(king_file + 1).min(7) {
+        let file = chess::get_file(File::from_index(file));
+        if (file & our_pawns) == chess::EMPTY {
+            safety += SEMI_OPEN_NEAR_KING;
+            if (file & pawns) == chess::EMPTY {
+                safety += OPEN_NEAR_KING;
+            }
+        }
+    }
+    safety
+}
+
+//==============================================================================
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::str::FromStr;
+
+    fn white(fen: &str) -> PieceTerms {
+        PieceTerms::of(&Board::from_str(fen).unwrap(), Color::White)
+    }
+
+    #[test]
+    fn mobility() {
+        // A knight in the corner reaches 2 squares and in the centre 8, and a rook on an empty board 14
+        assert_eq!(
+            white("4k3/8/8/8/8/8/8/N3K3 w - - 0 1").mobility,
+            MOBILITY[0] * 2
+        );
+        assert_eq!(
+            white("4k3/8/8/8/3N4/8/8/4K3 w - - 0 1").mobility,
+            MOBILITY[0] * 8
+        );
+        assert_eq!(
+            white("4k3/8/8/8/3R4/8/8/4K3 w - - 0 1").mobility,
+            MOBILITY[2] * 14
+        );
+        // Own pieces block, enemy pieces can be taken
+        assert_eq!(
+            white("4k3/8/8/8/P7/8/8/R3K3 w - - 0 1").mobility,
+            MOBILITY[2] * 5
+        );
+        assert_eq!(
+            white("4k3/8/8/8/p7/8/8/R3K3 w - - 0 1").mobility,
+            MOBILITY[2] * 6
+        );
+        assert_eq!(
+            white("4k3/8/8/8/8/8/8/B3K3 w - - 0 1").mobility,
+            MOBILITY[1] * 7
+        );
+        // The same for both sides at the start
+        let board = Board::default();
+        assert_eq!(
+            PieceTerms::of(&board, Color::White).mobility,
+            PieceTerms::of(&board, Color::Black).mobility
+        );
+    }
+
+    #[test]
+    fn king_safety() {
+        // A castled king behind its pawns, then with the pawns gone and a queen aiming at it
+        let sheltered = white("6k1/8/8/8/8/8/5PPP/6K1 w - - 0 1").king_safety;
+        assert_eq!(sheltered, PAWN_SHIELD * 3);
+        let exposed = white("6k1/8/8/8/8/8/8/q5K1 w - - 0 1").king_safety;
+        assert_eq!(
+            exposed,
+            (SEMI_OPEN_NEAR_KING + OPEN_NEAR_KING) * 3 + KING_ATTACKERS[3]
+        );
+        // An enemy pawn on a file leaves it semi-open, not open, and a pawn two ranks up still shields
+        let half = white("6k1/7p/8/8/8/6P1/5P2/6K1 w - - 0 1").king_safety;
+        assert_eq!(half, PAWN_SHIELD * 2 + SEMI_OPEN_NEAR_KING);
+        // A knight two squares away attacks the king's zone
+        let knight = white("6k1/8/8/8/8/5n2/5PPP/6K1 w - - 0 1").king_safety;
+        assert_eq!(knight, PAWN_SHIELD * 3 + KING_ATTACKERS[0]);
+    }
+
+    #[test]
+    fn bishops_rooks_and_knights() {
+        assert_eq!(
+            white("4k3/8/8/8/8/8/8/2B1KB2 w - - 0 1").bishop_pair,
+            BISHOP_PAIR
+        );
+        assert_eq!(
+            white("4k3/8/8/8/8/8/8/2B1KN2 w - - 0 1").bishop_pair,
+            Tapered::default()
+        );
+
+        // Open a-file, semi-open b-file with only a black pawn, and a closed c-file
+        assert_eq!(
+            white("4k3/8/8/8/8/8/8/R3K3 w - - 0 1").rook_files,
+            ROOK_OPEN_FILE
+        );
+        assert_eq!(
+            white("4k3/1p6/8/8/8/8/8/1R2K3 w - - 0 1").rook_files,
+            ROOK_SEMI_OPEN_FILE
+        );
+        assert_eq!(
+            white("4k3/2p5/8/8/8/8/2P5/2R1K3 w - - 0 1").rook_files,
+            Tapered::default()
+        );
+
+        // The seventh counts with the king on the eighth or pawns to attack, and from Black's side too
+        assert_eq!(
+            white("4k3/R7/8/8/8/8/8/4K3 w - - 0 1").rook_seventh,
+            ROOK_SEVENTH
+        );
+        assert_eq!(
+            white("8/R7/4k3/8/8/8/8/4K3 w - - 0 1").rook_seventh,
+            Tapered::default()
+        );
+        assert_eq!(
+            white("8/R5p1/4k3/8/8/8/8/4K3 w - - 0 1").rook_seventh,
+            ROOK_SEVENTH
+        );
+        let board = Board::from_str("4k3/8/8/8/8/8/r7/4K3 w - - 0 1").unwrap();
+        assert_eq!(
+            PieceTerms::of(&board, Color::Black).rook_seventh,
+            ROOK_SEVENTH
+        );
+
+        // A knight on e5 guarded by the d-pawn, until a black pawn on the f-file can drive it off
+        assert_eq!(
+            white("4k3/8/8/4N3/3P4/8/8/4K3 w - - 0 1").knight_outposts,
+            KNIGHT_OUTPOST
+        );
+        assert_eq!(
+            white("4k3/5p2/8/4N3/3P4/8/8/4K3 w - - 0 1").knight_outposts,
+            Tapered::default()
+        );
+        assert_eq!(
+            white("4k3/8/8/4N3/8/8/8/4K3 w - - 0 1").knight_outposts,
+            Tapered::default()
+        );
+    }
+
+    #[test]
+    fn weights() {
+        // Even at the start, and nothing when every term is left out
+        let board = Board::default();
+        assert_eq!(piece_terms(&board, &Weights::default()), Tapered::default());
+        let board = Board::from_str("4k3/8/8/8/3N4/8/8/2B1KB2 w - - 0 1").unwrap();
+        assert_eq!(piece_terms(&board, &Weights::none()), Tapered::default());
+
+        // Each weight scales its term alone
+        let terms = PieceTerms::of(&board, Color::White);
+        let pair = Weights {
+            bishop_pair: 50,
+            ..Weights::none()
+        };
+        assert_eq!(piece_terms(&board, &pair), Tapered::new(15, 25));
+        let doubled = Weights {
+            mobility: 200,
+            ..Weights::none()
+        };
+        assert_eq!(terms.weighted(&doubled), terms.mobility * 2);
+        let all = terms.weighted(&Weights::default())
+            - PieceTerms::of(&board, Color::Black).weighted(&Weights::default());
+        assert_eq!(piece_terms(&board, &Weights::default()), all);
+    }
+}
@@ -0,0 +1,136 @@
+use crate::score::Score;
+use crate::Turn;
+use chess::{Action, ChessMove, Game};
+
+/// Plies to wait after offering a draw before offering another.
+const OFFER_INTERVAL: usize = 20;
+
+/// When a search agent resigns or offers, accepts and claims draws, based on the score of its best move.
+#[derive(Copy, Clone, Default)]
+pub struct Thresholds {
+    /// Resign when the best score is below this.  When not set, never resign.
+    pub resign: Option<Score>,
+    /// Offer, accept and claim draws when the best score is within this of even.
+    /// When not set, claim a draw whenever possible and never offer or accept one.
+    pub draw: Option<Score>,
+}
+impl Thresholds {
+    /// Turn the best move and its score into the agent's decision for this turn.
+    pub fn turn(&self, game: &Game, best: Option<(ChessMove, Score)>) -> Turn {
+        let Some((the_move, score)) = best else {
+            return Turn::Resign;
+        };
+
+        if self.resign.is_some_and(|r| score < r) {
+            return Turn::Resign;
+        }
+
+        match self.draw {
+            None if game.can_declare_draw() => Turn::ClaimDraw,
+            Some(d) if score <= d && game.can_declare_draw() => Turn::ClaimDraw,
+            Some(d) if score.abs() <= d && !Thresholds::offered_recently(game) => {
+                Turn::OfferDraw(the_move)
+            }
+            _ => Turn::Move(the_move),
+        }
+    }
+
+    /// Accept a draw offer when the last known score is no better than the draw threshold.
+    pub fn accept(&self, score: Option<Score>) -> bool {
+        matches!((self.draw, score), (Some(d), Some(s)) if s <= d)
+    }
+
+    /// Avoid pestering the opponent with an offer every move.
+    fn offered_recently(game: &Game) -> bool {
+        let side = game.side_to_move();
+        game.actions()
+            .iter()
+            .rev()
+            .take(OFFER_INTERVAL)
+            .any(|a| *a == Action::OfferDraw(side))
+    }
+}
+
+//==============================================================================
+#[cfg(test)]
+mod test {
+    use super::*;
+    use chess::Square;
+
+    /// Both sides shuffle knights until the position has repeated three times
+    fn repeated_game() -> Game {
+        let mut game = Game::new();
+        for _ in 0..2 {
+            assert!(game.make_move(ChessMove::new(Square::G1, Square::F3, None)));
+            assert!(game.make_move(ChessMove::new(Square::G8, Square::F6, None)));
+            assert!(game.make_move(ChessMove::new(Square::F3, Square::G1, None)));
+            assert!(game.make_move(ChessMove::new(Square::F6, Square::G8, None)));
+        }
+        assert!(game.can_declare_draw());
+        game
+    }
+
+    #[test]
+    fn default_thresholds() {
+        let e2e4 = ChessMove::new(Square::E2, Square::E4, None);
+        let t = Thresholds::default();
+
+        // Never resign, even when mated next move, never offer or accept, but claim as soon as possible
+        assert_eq!(
+            t.turn(&Game::new(), Some((e2e4, Score::mated_in(1)))),
+            Turn::Move(e2e4)
+        );
+        assert_eq!(t.turn(&Game::new(), None), Turn::Resign);
+        assert_eq!(
+            t.turn(&repeated_game(), Some((e2e4, Score::cp(500)))),
+            Turn::ClaimDraw
+        );
+        assert!(!t.accept(Some(Score::cp(-500))));
+    }
+
+    #[test]
+    fn resign_and_draw() {
+        let e2e4 = ChessMove::new(Square::E2, Square::E4, None);
+        let t = Thresholds {
+            resign: Some(Score::cp(-300)),
+            draw: Some(Score::cp(20)),
+        };
+
+        assert_eq!(
+            t.turn(&Game::new(), Some((e2e4, Score::cp(-301)))),
+            Turn::Resign
+        );
+        assert_eq!(
+            t.turn(&Game::new(), Some((e2e4, Score::cp(-100)))),
+            Turn::Move(e2e4)
+        );
+        assert_eq!(
+            t.turn(&Game::new(), Some((e2e4, Score::cp(10)))),
+            Turn::OfferDraw(e2e4)
+        );
+        assert_eq!(
+            t.turn(&Game::new(), Some((e2e4, Score::cp(100)))),
+            Turn::Move(e2e4)
+        );
+
+        // Only claim a repetition when not winning
+        assert_eq!(
+            t.turn(&repeated_game(), Some((e2e4, Score::cp(0)))),
+            Turn::ClaimDraw
+        );
+        assert_eq!(
+            t.turn(&repeated_game(), Some((e2e4, Score::cp(100)))),
+            Turn::Move(e2e4)
+        );
+
+        // No second offer straight after the first
+        let mut game = Game::new();
+        assert!(game.offer_draw(chess::Color::White));
+        assert_eq!(t.turn(&game, Some((e2e4, Score::cp(10)))), Turn::Move(e2e4));
+
+        assert!(t.accept(Some(Score::cp(20))));
+        assert!(t.accept(Some(Score::cp(-500))));
+        assert!(!t.accept(Some(Score::cp(21))));
+        assert!(!t.accept(None));
+    }
+}
@@ -0,0 +1,214 @@
+use crate::pawns::PawnTerms;
+use crate::score::{Score, Tapered, MAX_PHASE};
+use crate::terms::PieceTerms;
+use chess::{Color, Piece, Square};
+use std::fmt;
+
+const PIECE_NAMES: [&str; 6] = ["pawn", "knight", "bishop", "rook", "queen", "king"];
+
+/// What one piece gets from the piece-square tables, for its own side.
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub struct TableEntry {
+    pub color: Color,
+    pub piece: Piece,
+    pub square: Square,
+    pub value: i32,
+}
+
+/// A board's score broken down by term, piece and side, from `Calc::trace`, to see why a board scores as it does.
+/// Printing it lays out every part and how they add up.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct Trace {
+    /// From `MAX_PHASE` with every piece on, down to 0 with only kings and pawns.
+    pub phase: i32,
+    /// Every piece, White's first.
+    pub tables: Vec<TableEntry>,
+    /// The pawn structure for White and Black, or `None` when it is turned off.
+    pub pawns: Option<[PawnTerms; 2]>,
+    /// The piece terms for White and Black, already weighted.
+    pub pieces: [PieceTerms; 2],
+    /// The score for White, as `Calc::score_board` gives it.
+    pub total: Score,
+}
+impl Trace {
+    /// The table values of the side's pieces.
+    pub fn tables(&self, color: Color) -> i32 {
+        self.tables
+            .iter()
+            .filter(|e| e.color == color)
+            .map(|e| e.value)
+            .sum()
+    }
+
+    /// Every term, with its name and its values for White and Black.
+    pub fn terms(&self) -> Vec<(&'static str, Tapered, Tapered)> {
+        let mut terms = Vec::new();
+        if let Some([white, black]) = &self.pawns {
+            terms.extend(
+                white
+                    .named()
+                    .into_iter()
+                    .zip(black.named())
+                    .map(|((name, w), (_, b))| (name, w, b)),
+            );
+        }
+        let [white, black] = &self.pieces;
+        terms.extend(
+            white
+                .named()
+                .into_iter()
+                .zip(black.named())
+                .map(|((name, w), (_, b))| (name, w, b)),
+        );
+        terms
+    }
+
+    /// The terms for White less those for Black, before blending by phase.
+    pub fn terms_total(&self) -> Tapered {
+        self.terms()
+            .into_iter()
+            .fold(Tapered::default(), |sum, (_, white, black)| {
+                sum + white - black
+            })
+    }
+}
+impl fmt::Display for Trace {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "phase {} of {MAX_PHASE}", self.phase)?;
+        writeln!(f)?;
+        writeln!(
+            f,
+            "{:<24}{:>7}{:>7}",
+            "piece-square tables", "white", "black"
+        )?;
+        for entry in &self.tables {
+            let name = format!("  {} {}", PIECE_NAMES[entry.piece.to_index()], entry.square);
+            match entry.color {
+                Color::White => writeln!(f, "{name:<24}{:>7}", entry.value)?,
+                Color::Black => writeln!(f, "{name:<24}{:>14}", entry.value)?,
+            }
+        }
+        let (white, black) = (self.tables(Color::White), self.tables(Color::Black));
+        writeln!(
+            f,
+            "{:<24}{white:>7}{black:>7}{:>9}",
+            "  tables",
+            white - black
+        )?;
+        writeln!(f)?;
+
+        writeln!(
+            f,
+            "{:<24}{:>14}{:>14}{:>9}",
+            "terms (mg/eg)", "white", "black", "blended"
+        )?;
+        for (name, white, black) in self.terms() {
+            let w = format!("{}/{}", white.middlegame, white.endgame);
+            let b = format!("{}/{}", black.middlegame, black.endgame);
+            writeln!(
+                f,
+                "  {name:<22}{w:>14}{b:>14}{:>9}",
+                (white - black).blend(self.phase)
+            )?;
+        }
+        let terms = self.terms_total();
+        let net = format!("{}/{}", terms.middlegame, terms.endgame);
+        writeln!(
+            f,
+            "{:<24}{net:>28}{:>9}",
+            "  terms",
+            terms.blend(self.phase)
+        )?;
+        writeln!(f)?;
+        write!(f, "total for White: {}", self.total)
+    }
+}
+
+//==============================================================================
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::score::Calc;
+    use crate::terms::Weights;
+    use chess::Board;
+    use std::str::FromStr;
+
+    const POSITIONS: [&str; 3] = [
+        "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1",
+        "4k3/2p5/1P6/8/3N4/2B5/5PPP/R5K1 b - - 0 1",
+        "4k3/8/8/8/8/8/8/4K3 w - - 0 1",
+    ];
+
+    /// The tables and the blended terms add up to the score.
+    fn adds_up(trace: &Trace) {
+        let tables = trace.tables(Color::White) - trace.tables(Color::Black);
+        assert_eq!(
+            Score::cp(tables + trace.terms_total().blend(trace.phase)),
+            trace.total
+        );
+    }
+
+    #[test]
+    fn breakdown() {
+        let mut calc = Calc::with_pawn_structure();
+        calc.set_weights(Weights::default());
+        for fen in POSITIONS {
+            let board = Board::from_str(fen).unwrap();
+            let trace = calc.trace(&board);
+            assert_eq!(trace.total, calc.score_board(&board, true));
+            assert_eq!(trace.tables.len(), board.combined().popcnt() as usize);
+            assert_eq!(trace.terms().len(), 11);
+            adds_up(&trace);
+        }
+
+        // The knights at the start stand on -40 squares, and everything else is even
+        let trace = calc.trace(&Board::default());
+        let knight = TableEntry {
+            color: Color::White,
+            piece: Piece::Knight,
+            square: Square::B1,
+            value: -40,
+        };
+        assert!(trace.tables.contains(&knight));
+        assert_eq!((trace.phase, trace.total), (MAX_PHASE, Score::ZERO));
+    }
+
+    #[test]
+    fn terms_turned_off() {
+        // Without pawn structure its terms are left out, and the piece terms are weighted, down to zero
+        let mut calc = Calc::new();
+        calc.set_weights(Weights {
+            mobility: 50,
+            ..Weights::none()
+        });
+        let board = Board::from_str(POSITIONS[0]).unwrap();
+        let trace = calc.trace(&board);
+        assert_eq!(trace.pawns, None);
+        assert_eq!(trace.terms().len(), 6);
+        assert_eq!(trace.pieces[0].bishop_pair, Tapered::default());
+        let half = PieceTerms::of(&board, Color::White).scaled(&Weights {
+            mobility: 50,
+            ..Weights::none()
+        });
+        assert_eq!(trace.pieces[0], half);
+        adds_up(&trace);
+    }
+
+    #[test]
+    fn display() {
+        let mut calc = Calc::with_pawn_structure();
+        calc.set_weights(Weights::default());
+        let text = calc
+            .trace(&Board::from_str(POSITIONS[1]).unwrap())
+            .to_string();
+        for line in [
+            "phase 4 of 24",
+            "  knight d4",
+            "  passed pawns",
+            "  bishop pair",
+            "total for White: cp ",
+        ] {
+            assert!(text.contains(line), "{line}\n{text}");
+        }
+    }
+}
@@ -0,0 +1,276 @@
+use crate::eval::NodeState;
+use crate::score::Score;
+use chess::ChessMove;
+use std::ops::{Index, IndexMut, Range};
+
+/// Nodes kept between moves unless an agent sets its own budget, about 100 MB.
+pub const DEFAULT_NODE_BUDGET: usize = 4_000_000;
+
+/// Tree search node containing move, score and the evaluator's state.  A node's children sit next to each other in the tree's arena.
+#[derive(Copy, Clone, Debug)]
+pub(crate) struct Node {
+    pub the_move: Option<ChessMove>,
+    pub score: Score,
+    pub propagate_score: Score,
+    pub state: NodeState,
+    first_child: u32,
+    child_count: u32,
+}
+impl Node {
+    fn new(the_move: Option<ChessMove>, score: Score, state: NodeState) -> Self {
+        Self {
+            the_move,
+            score,
+            propagate_score: score,
+            state,
+            first_child: 0,
+            child_count: 0,
+        }
+    }
+
+    /// The node with its children moved `offset` places along the arena.
+    fn moved(mut self, offset: u32) -> Self {
+        if self.child_count > 0 {
+            self.first_child += offset;
+        }
+        self
+    }
+}
+
+/// How much memory a search tree holds.
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub struct TreeSize {
+    pub nodes: usize,
+    pub bytes: usize,
+}
+
+/// Search tree with every node in one vector, indexed from the root at 0.  This avoids an allocation per node, and
+/// dropping or copying a subtree only touches one vector.
+pub(crate) struct Tree {
+    nodes: Vec<Node>,
+}
+impl Tree {
+    /// Create a tree with just a root.
+    pub fn new(score: Score, state: NodeState) -> Self {
+        Tree {
+            nodes: vec![Node::new(None, score, state)],
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.nodes.len()
+    }
+
+    /// Nodes in use and bytes allocated, including nodes left unreachable by `graft`.
+    pub fn size(&self) -> TreeSize {
+        TreeSize {
+            nodes: self.nodes.len(),
+            bytes: std::mem::size_of::<Self>()
+                + self.nodes.capacity() * std::mem::size_of::<Node>(),
+        }
+    }
+
+    /// Indexes of the node's children.
+    pub fn children(&self, node: usize) -> Range<usize> {
+        let n = &self.nodes[node];
+        n.first_child as usize..(n.first_child + n.child_count) as usize
+    }
+
+    /// Index of the child reached by the move, if it was expanded.
+    pub fn child(&self, node: usize, the_move: ChessMove) -> Option<usize> {
+        self.children(node)
+            .find(|&c| self.nodes[c].the_move == Some(the_move))
+    }
+
+    /// Give a leaf its children, with the score and state of each move.
+    pub fn add_children(
+        &mut self,
+        node: usize,
+        moves: impl IntoIterator<Item = (ChessMove, Score, NodeState)>,
+    ) {
+        let first = self.nodes.len();
+        self.nodes.extend(
+            moves
+                .into_iter()
+                .map(|(m, s, state)| Node::new(Some(m), s, state)),
+        );
+        self.nodes[node].first_child = first as u32;
+        self.nodes[node].child_count = (self.nodes.len() - first) as u32;
+    }
+
+    /// Average of the children's propagated scores.
+    pub fn average(&self, node: usize) -> Score {
+        Score::average(self.children(node).map(|c| self.nodes[c].propagate_score))
+    }
+
+    /// The line below the node where each side plays its best child, the side choosing at the node maximizing the
+    /// propagated score if `maximize` is set.  Ties go to the first child, as in the search.
+    pub fn principal_variation(&self, mut node: usize, mut maximize: bool) -> Vec<ChessMove> {
+        let mut pv = Vec::new();
+        while let Some(best) = self.children(node).reduce(|a, b| {
+            let (a_score, b_score) = (self.nodes[a].propagate_score, self.nodes[b].propagate_score);
+            if (maximize && b_score > a_score) || (!maximize && b_score < a_score) {
+                b
+            } else {
+                a
+            }
+        }) {
+            pv.push(self.nodes[best].the_move.unwrap());
+            node = best;
+            maximize = !maximize;
+        }
+        pv
+    }
+
+    /// Copy the node and everything below it into a new tree, leaving out unreachable nodes.
+    pub fn subtree(&self, node: usize) -> Tree {
+        let mut copy = Tree {
+            nodes: vec![self.nodes[node]],
+        };
+        self.copy_children(node, &mut copy, 0);
+        copy
+    }
+
+    fn copy_children(&self, node: usize, copy: &mut Tree, at: usize) {
+        let children = self.children(node);
+        let first = copy.nodes.len();
+        copy.nodes[at].first_child = first as u32;
+        copy.nodes.extend(children.clone().map(|c| self.nodes[c]));
+        for (i, c) in children.enumerate() {
+            self.copy_children(c, copy, first + i);
+        }
+    }
+
+    /// Replace the node with the root of another tree, appending the rest of it.  The node's old descendants are left
+    /// unreachable until the tree is next copied.
+    pub fn graft(&mut self, node: usize, sub: Tree) {
+        let offset = self.nodes.len() as u32 - 1;
+        self.nodes[node] = sub.nodes[0].moved(offset);
+        self.nodes
+            .extend(sub.nodes[1..].iter().map(|n| n.moved(offset)));
+    }
+
+    /// Shrink the tree to at most `budget` nodes, if possible by keeping only the root's children.  The subtrees under
+    /// the root moves with the lowest propagated scores go first, since the agent is least likely to play them.
+    pub fn prune(&mut self, budget: usize) {
+        let mut roots: Vec<(Score, usize, usize)> = self
+            .children(0)
+            .map(|c| (self.nodes[c].propagate_score, c, self.reachable(c) - 1))
+            .collect();
+        roots.sort_unstable();
+
+        let mut total = 1 + roots.len() + roots.iter().map(|&(_, _, below)| below).sum::<usize>();
+        for (_, c, below) in roots {
+            if total <= budget {
+                break;
+            }
+            self.nodes[c].child_count = 0;
+            total -= below;
+        }
+        *self = self.subtree(0);
+    }
+
+    /// Count the node and its descendants.
+    fn reachable(&self, node: usize) -> usize {
+        1 + self
+            .children(node)
+            .map(|c| self.reachable(c))
+            .sum::<usize>()
+    }
+}
+impl Index<usize> for Tree {
+    type Output = Node;
+
+    fn index(&self, node: usize) -> &Node {
+        &self.nodes[node]
+    }
+}
+impl IndexMut<usize> for Tree {
+    fn index_mut(&mut self, node: usize) -> &mut Node {
+        &mut self.nodes[node]
+    }
+}
+
+//==============================================================================
+#[cfg(test)]
+mod test {
+    use super::*;
+    use chess::{Board, MoveGen};
+
+    /// A tree with the 20 opening moves, and the 20 replies to the first two
+    fn opening() -> Tree {
+        let board = Board::default();
+        let mut tree = Tree::new(Score::ZERO, NodeState::default());
+        tree.add_children(
+            0,
+            MoveGen::new_legal(&board)
+                .enumerate()
+                .map(|(i, m)| (m, Score::cp(i as i32), NodeState::default())),
+        );
+        for c in [0, 1].map(|i| tree.children(0).start + i) {
+            let reply = board.make_move_new(tree[c].the_move.unwrap());
+            tree.add_children(
+                c,
+                MoveGen::new_legal(&reply).map(|m| (m, Score::ZERO, NodeState::default())),
+            );
+        }
+        tree
+    }
+
+    #[test]
+    fn children() {
+        let tree = opening();
+        assert_eq!(tree.len(), 61);
+        assert_eq!(tree.children(0).len(), 20);
+        assert_eq!(tree.average(0), Score::cp(9));
+
+        let first = tree.children(0).start;
+        let m = tree[first].the_move.unwrap();
+        assert_eq!(tree.child(0, m), Some(first));
+        assert_eq!(tree.children(first).len(), 20);
+        assert!(tree.children(first + 2).is_empty());
+        assert_eq!(tree.size().nodes, 61);
+
+        // The highest root move has no replies, and the lowest leads to the first of its even replies
+        assert_eq!(
+            tree.principal_variation(0, true),
+            [tree[first + 19].the_move.unwrap()]
+        );
+        let pv = tree.principal_variation(0, false);
+        assert_eq!(pv, [m, tree[tree.children(first).start].the_move.unwrap()]);
+    }
+
+    #[test]
+    fn subtree_and_graft() {
+        let mut tree = opening();
+        let first = tree.children(0).start;
+
+        let sub = tree.subtree(first);
+        assert_eq!(sub.len(), 21);
+        assert_eq!(sub[0].the_move, tree[first].the_move);
+
+        // Grafting a subtree back leaves the old copy unreachable until the tree is copied
+        let mut sub = tree.subtree(first);
+        sub[0].propagate_score = Score::cp(99);
+        tree.graft(first, sub);
+        assert_eq!(tree.len(), 81);
+        assert_eq!(tree[first].propagate_score, Score::cp(99));
+        assert_eq!(tree.children(first).len(), 20);
+        assert_eq!(tree.subtree(0).len(), 61);
+    }
+
+    #[test]
+    fn prune() {
+        // Only the second root move's replies fit, and its score is higher
+        let mut tree = opening();
+        tree.prune(50);
+        assert_eq!(tree.len(), 41);
+        let second = tree.children(0).start + 1;
+        assert!(tree.children(tree.children(0).start).is_empty());
+        assert_eq!(tree.children(second).len(), 20);
+
+        // A budget below the root moves keeps them anyway
+        tree.prune(5);
+        assert_eq!(tree.len(), 21);
+    }
+}
@@ -0,0 +1,184 @@
+use crate::score::Score;
+use chess::{ChessMove, ALL_PIECES, ALL_SQUARES};
+use std::sync::atomic::{AtomicU64, Ordering};
+
+/// Whether a stored score is exact or only a bound from a cutoff.
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum Bound {
+    Exact,
+    /// The score is at least this (the search failed high).
+    Lower,
+    /// The score is at most this (no move raised alpha).
+    Upper,
+}
+
+/// What the search learned about a position.  Mate scores count their plies from the stored position.
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub struct Entry {
+    pub the_move: Option<ChessMove>,
+    pub score: Score,
+    pub depth: u8,
+    pub bound: Bound,
+}
+impl Entry {
+    fn pack(self) -> u64 {
+        let m = self.the_move.map_or(0, |m| {
+            let promotion = m.get_promotion().map_or(0, |p| p.to_index() as u64 + 1);
+            1 | (m.get_source().to_index() as u64) << 1
+                | (m.get_dest().to_index() as u64) << 7
+                | promotion << 13
+        });
+        let bound = match self.bound {
+            Bound::Exact => 0,
+            Bound::Lower => 1,
+            Bound::Upper => 2,
+        };
+        // The top bit marks the slot as used, since an entry could otherwise pack to zero
+        let score = u64::from(self.score.centipawns() as u32);
+        m | score << 16 | u64::from(self.depth) << 48 | bound << 56 | 1 << 63
+    }
+
+    fn unpack(data: u64) -> Self {
+        let the_move = (data & 1 == 1).then(|| {
+            let promotion = (data >> 13) & 7;
+            ChessMove::new(
+                ALL_SQUARES[((data >> 1) & 63) as usize],
+                ALL_SQUARES[((data >> 7) & 63) as usize],
+                (promotion > 0).then(|| ALL_PIECES[promotion as usize - 1]),
+            )
+        });
+        let bound = match (data >> 56) & 3 {
+            0 => Bound::Exact,
+            1 => Bound::Lower,
+            _ => Bound::Upper,
+        };
+        Entry {
+            the_move,
+            score: Score((data >> 16) as u32 as i32),
+            depth: (data >> 48) as u8,
+            bound,
+        }
+    }
+}
+
+/// A transposition table that many search threads read and write without locks.
+/// Each slot keeps the key XORed with the data, so a slot torn by two threads writing at once fails the key check
+/// instead of returning another position's entry.
+pub struct TranspositionTable {
+    slots: Vec<[AtomicU64; 2]>,
+}
+impl TranspositionTable {
+    /// Create a table using about `megabytes` of memory, rounded down to a power of two slots.
+    pub fn new(megabytes: usize) -> Self {
+        let slots = megabytes.max(1) * 1024 * 1024 / std::mem::size_of::<[AtomicU64; 2]>();
+        let slots = 1 << (usize::BITS - 1 - slots.leading_zeros());
+        TranspositionTable {
+            slots: (0..slots)
+                .map(|_| [AtomicU64::new(0), AtomicU64::new(0)])
+                .collect(),
+        }
+    }
+
+    /// Look up a position by its Zobrist hash.
+    pub fn probe(&self, hash: u64) -> Option<Entry> {
+        let slot = &self.slots[self.index(hash)];
+        let data = slot[1].load(Ordering::Relaxed);
+        (data != 0 && slot[0].load(Ordering::Relaxed) ^ data == hash).then(|| Entry::unpack(data))
+    }
+
+    /// Store an entry, keeping a deeper entry for the same position unless the new one is exact.
+    pub fn store(&self, hash: u64, entry: Entry) {
+        if let Some(old) = self.probe(hash) {
+            if old.depth > entry.depth && entry.bound != Bound::Exact {
+                return;
+            }
+        }
+        let slot = &self.slots[self.index(hash)];
+        let data = entry.pack();
+        slot[0].store(hash ^ data, Ordering::Relaxed);
+        slot[1].store(data, Ordering::Relaxed);
+    }
+
+    /// Parts per thousand of the table in use, from a sample of its slots.
+    pub fn hashfull(&self) -> u16 {
+        let sample = &self.slots[..self.slots.len().min(1000)];
+        (sample
+            .iter()
+            .filter(|s| s[1].load(Ordering::Relaxed) != 0)
+            .count()
+            * 1000
+            / sample.len()) as u16
+    }
+
+    /// Forget every entry.
+    pub fn clear(&self) {
+        for slot in &self.slots {
+            slot[0].store(0, Ordering::Relaxed);
+            slot[1].store(0, Ordering::Relaxed);
+        }
+    }
+
+    fn index(&self, hash: u64) -> usize {
+        (hash as usize) & (self.slots.len() - 1)
+    }
+}
+
+//==============================================================================
+#[cfg(test)]
+mod test {
+    use super::*;
+    use chess::{Piece, Square};
+
+    #[test]
+    fn store_and_probe() {
+        let tt = TranspositionTable::new(1);
+        let entry = Entry {
+            the_move: Some(ChessMove::new(Square::A7, Square::A8, Some(Piece::Knight))),
+            score: Score::cp(-123),
+            depth: 5,
+            bound: Bound::Lower,
+        };
+        assert_eq!(tt.probe(42), None);
+        tt.store(42, entry);
+        assert_eq!(tt.probe(42), Some(entry));
+
+        // A shallower bound does not replace a deeper entry, but an exact score does
+        let shallow = Entry {
+            the_move: None,
+            score: Score::cp(7),
+            depth: 2,
+            bound: Bound::Upper,
+        };
+        tt.store(42, shallow);
+        assert_eq!(tt.probe(42), Some(entry));
+        let exact = Entry {
+            bound: Bound::Exact,
+            ..shallow
+        };
+        tt.store(42, exact);
+        assert_eq!(tt.probe(42), Some(exact));
+
+        // Mate scores keep their distance
+        let mate = Entry {
+            score: Score::mated_in(7),
+            ..exact
+        };
+        tt.store(42, mate);
+        assert_eq!(tt.probe(42), Some(mate));
+
+        // Another key in the same slot misses until it is stored
+        let other = 42 + tt.slots.len() as u64;
+        assert_eq!(tt.probe(other), None);
+        tt.store(other, entry);
+        assert_eq!(tt.probe(other), Some(entry));
+        assert_eq!(tt.probe(42), None);
+
+        tt.clear();
+        assert_eq!(tt.probe(other), None);
+
+        // One slot in the thousand sampled
+        assert_eq!(tt.hashfull(), 0);
+        tt.store(0, entry);
+        assert_eq!(tt.hashfull(), 1);
+    }
+}
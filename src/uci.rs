@@ -0,0 +1,520 @@
+use crate::info::SearchInfo;
+use crate::limit::{Deadline, Limit};
+use crate::{agent_by_name, Start, Thresholds};
+use chess::{ChessMove, Game, MoveGen};
+use std::io::{BufRead, Write};
+use std::str::FromStr;
+use std::sync::atomic::{AtomicBool, AtomicU8, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread::JoinHandle;
+use std::time::Duration;
+
+/// Depth searched by `go` without a depth or time limit.
+const DEFAULT_DEPTH: u8 = 4;
+/// Most lines the `MultiPV` option allows.
+const MAX_MULTIPV: usize = 64;
+/// Deepest a search with a time limit, or until `stop`, goes.
+const MAX_DEPTH: u8 = 64;
+
+/// A UCI (Universal Chess Interface) engine playing with one of the agents, so it can run under a chess GUI.
+/// Commands come in as lines of text, and replies and search `info` lines go to the output.  Searches run in the
+/// background, so `stop` and `ponderhit` can arrive while they do.
+pub struct Uci<W: Write + Send + 'static> {
+    agent: String,
+    /// Where the game from `position` began, for finding repetitions of positions before it.
+    start: Start,
+    game: Game,
+    /// Best moves to report, from the `MultiPV` option.
+    multipv: usize,
+    output: Arc<Mutex<W>>,
+    search: Option<Search>,
+}
+
+/// A `go` running in the background.
+struct Search {
+    thread: JoinHandle<()>,
+    control: Arc<Control>,
+}
+
+/// How the engine steers a running search.
+struct Control {
+    /// Set by `stop`: answer with the best move found so far.
+    stop: Arc<AtomicBool>,
+    /// Set by `go ponder` until `ponderhit`, while the search ignores its time limit and must not answer.
+    pondering: AtomicBool,
+    /// The time limit, which starts at `go`, or at `ponderhit` when pondering.
+    budget: Option<Duration>,
+    /// Sets `stop` when the time is up.
+    deadline: Mutex<Option<Deadline>>,
+}
+impl Control {
+    /// Start the clock on the time limit, if there is one.
+    fn start_clock(&self) {
+        if let Some(budget) = self.budget {
+            *self.deadline.lock().unwrap() = Some(Deadline::new(budget, self.stop.clone()));
+        }
+    }
+}
+
+/// What a `go` searches for and how long.
+struct Go {
+    agent: String,
+    start: Start,
+    game: Game,
+    multipv: usize,
+    /// `None` searches until `stop`.
+    limit: Option<Limit>,
+}
+
+impl<W: Write + Send + 'static> Uci<W> {
+    /// An engine using the agent with this command line name, or `None` if there is no such agent.
+    pub fn new(agent: &str, output: W) -> Option<Self> {
+        agent_by_name(agent, 1, Thresholds::default())?;
+        Some(Uci {
+            agent: agent.to_string(),
+            start: Start::default(),
+            game: Game::new(),
+            multipv: 1,
+            output: Arc::new(Mutex::new(output)),
+            search: None,
+        })
+    }
+
+    /// Handle commands until `quit` or the end of the input, where a search still running finishes first.
+    pub fn run(&mut self, input: impl BufRead) {
+        for line in input.lines() {
+            if !self.command(&line.unwrap()) {
+                return;
+            }
+        }
+        if let Some(search) = &self.search {
+            search.control.pondering.store(false, Ordering::Relaxed);
+        }
+        self.wait();
+    }
+
+    /// Handle one command, returning false for `quit`.  Unknown commands are ignored, as UCI asks.
+    pub fn command(&mut self, line: &str) -> bool {
+        let words: Vec<&str> = line.split_whitespace().collect();
+        match words.first().copied() {
+            Some("uci") => {
+                self.send(&format!("id name chess_ai {}", self.agent));
+                self.send(&format!(
+                    "option name MultiPV type spin default 1 min 1 max {MAX_MULTIPV}"
+                ));
+                self.send("option name Ponder type check default false");
+                self.send("uciok");
+            }
+            Some("isready") => self.send("readyok"),
+            Some("ucinewgame") => (self.start, self.game) = (Start::default(), Game::new()),
+            Some("position") => {
+                if let Some((start, game)) = position(&words[1..]) {
+                    (self.start, self.game) = (start, game);
+                }
+            }
+            Some("setoption") => self.set_option(&words[1..]),
+            Some("go") => self.go(&words[1..]),
+            Some("ponderhit") => {
+                if let Some(search) = &self.search {
+                    if search.control.pondering.swap(false, Ordering::Relaxed) {
+                        search.control.start_clock();
+                    }
+                }
+            }
+            Some("stop") => self.stop(),
+            Some("quit") => {
+                self.stop();
+                return false;
+            }
+            _ => {}
+        }
+        true
+    }
+
+    /// Start searching the current position in the background, for `go [ponder] [infinite] <limits>`.
+    fn go(&mut self, args: &[&str]) {
+        self.stop();
+        let limit = (!args.contains(&"infinite")).then(|| go_limit(args, self.game.side_to_move()));
+        let pondering = args.contains(&"ponder");
+        let control = Arc::new(Control {
+            stop: Arc::new(AtomicBool::new(false)),
+            pondering: AtomicBool::new(pondering),
+            budget: match limit {
+                Some(Limit::Time(budget)) => Some(budget),
+                _ => None,
+            },
+            deadline: Mutex::new(None),
+        });
+        if !pondering {
+            control.start_clock();
+        }
+        let go = Go {
+            agent: self.agent.clone(),
+            start: self.start,
+            game: self.game.clone(),
+            multipv: self.multipv,
+            limit,
+        };
+        let (output, thread_control) = (self.output.clone(), control.clone());
+        let thread = std::thread::spawn(move || search(go, &thread_control, &output));
+        self.search = Some(Search { thread, control });
+    }
+
+    /// Stop any search, which answers with its best move.
+    fn stop(&mut self) {
+        if let Some(search) = &self.search {
+            search.control.pondering.store(false, Ordering::Relaxed);
+            search.control.stop.store(true, Ordering::Relaxed);
+        }
+        self.wait();
+    }
+
+    fn wait(&mut self) {
+        if let Some(search) = self.search.take() {
+            search.thread.join().unwrap();
+        }
+    }
+
+    /// Handle `setoption name <name> value <value>`, ignoring options the engine does not have.
+    fn set_option(&mut self, args: &[&str]) {
+        let value_at = args
+            .iter()
+            .position(|a| *a == "value")
+            .unwrap_or(args.len());
+        let name = args
+            .get(1..value_at)
+            .map(|n| n.join(" "))
+            .unwrap_or_default();
+        let value = args
+            .get(value_at + 1..)
+            .map(|v| v.join(" "))
+            .unwrap_or_default();
+        if name.eq_ignore_ascii_case("MultiPV") {
+            if let Ok(lines) = value.parse::<usize>() {
+                self.multipv = lines.clamp(1, MAX_MULTIPV);
+            }
+        } else if name.eq_ignore_ascii_case("Ponder") {
+            // Only tells the engine whether `go ponder` may come, which needs nothing set up beforehand
+        }
+    }
+
+    fn send(&self, line: &str) {
+        send(&self.output, line);
+    }
+}
+
+fn send<W: Write>(output: &Mutex<W>, line: &str) {
+    let mut output = output.lock().unwrap();
+    writeln!(output, "{line}").unwrap();
+    output.flush().unwrap();
+}
+
+/// Search with one agent until the limit, streaming `info` lines.  An agent that deepens by itself searches once to
+/// the limit; any other deepens a ply at a time, keeping its tree, and a ply cut short by `stop` is thrown away.  The
+/// first ply of a search without a depth limit always finishes, so there is a move however soon it is stopped.  A
+/// pondering search keeps deepening and only answers after `ponderhit` or `stop`, with `bestmove <move> ponder
+/// <reply>` when it expects a reply.
+fn search<W: Write + Send + 'static>(go: Go, control: &Control, output: &Arc<Mutex<W>>) {
+    let mut agent = agent_by_name(&go.agent, 1, Thresholds::default()).unwrap();
+    agent.set_start(go.start);
+    // An agent deepening a ply at a time reports the shallower plies again, so only report depths not seen before
+    let reported = Arc::new(AtomicU8::new(0));
+    if go.multipv == 1 {
+        let (output, reported) = (output.clone(), reported.clone());
+        agent.set_info_callback(Box::new(move |info| {
+            if info.depth > reported.fetch_max(info.depth, Ordering::Relaxed) {
+                send(&output, &format!("info {info}"));
+            }
+        }));
+    }
+    let plies: Vec<u8> = match go.limit {
+        Some(Limit::Depth(depth)) => vec![depth],
+        _ if agent.deepens() => vec![1, MAX_DEPTH],
+        _ => (1..=MAX_DEPTH).collect(),
+    };
+
+    let mut best: Vec<SearchInfo> = Vec::new();
+    for depth in plies {
+        let finish = depth == 1 && !matches!(go.limit, Some(Limit::Depth(_)));
+        agent.set_stop_flag(if finish {
+            Arc::default()
+        } else {
+            control.stop.clone()
+        });
+        agent.set_depth(depth);
+        let lines = agent.analyze(&go.game, go.multipv);
+        let stopped = control.stop.load(Ordering::Relaxed);
+        if stopped && !best.is_empty() && (lines.is_empty() || !agent.deepens()) {
+            break;
+        }
+        for (i, info) in lines.iter().enumerate() {
+            if go.multipv > 1 {
+                send(output, &format!("info multipv {} {info}", i + 1));
+            } else if info.depth > reported.fetch_max(info.depth, Ordering::Relaxed) {
+                send(output, &format!("info {info}"));
+            }
+        }
+        best = lines;
+        if best.is_empty() || stopped {
+            break;
+        }
+    }
+    while control.pondering.load(Ordering::Relaxed) && !control.stop.load(Ordering::Relaxed) {
+        std::thread::sleep(Duration::from_millis(1));
+    }
+
+    // Any legal move if stopped before the first depth finished, and a null move when there is nothing to play
+    match best.first().map(|info| &info.pv[..]) {
+        Some([m, reply, ..]) => send(output, &format!("bestmove {m} ponder {reply}")),
+        Some([m, ..]) => send(output, &format!("bestmove {m}")),
+        _ => match MoveGen::new_legal(&go.game.current_position()).next() {
+            Some(m) => send(output, &format!("bestmove {m}")),
+            None => send(output, "bestmove 0000"),
+        },
+    }
+}
+
+/// Where the game for `position [startpos | fen <fen>] [moves <move>...]` began and the game, or `None` if it is not
+/// valid.
+fn position(args: &[&str]) -> Option<(Start, Game)> {
+    let moves_at = args
+        .iter()
+        .position(|a| *a == "moves")
+        .unwrap_or(args.len());
+    let start = match args.first() {
+        Some(&"startpos") => Start::default(),
+        Some(&"fen") => Start::from_fen(&args[1..moves_at].join(" "))?,
+        _ => return None,
+    };
+    let mut game = Game::new_with_board(start.board);
+    for m in args.iter().skip(moves_at + 1) {
+        let m = ChessMove::from_str(m).ok()?;
+        if !game.make_move(m) {
+            return None;
+        }
+    }
+    Some((start, game))
+}
+
+/// The limit for `go [depth <n>] [movetime <ms>] [wtime <ms> btime <ms> winc <ms> binc <ms>]`.  With a clock, spend
+/// a thirtieth of the time left plus the increment.
+fn go_limit(args: &[&str], side: chess::Color) -> Limit {
+    let value = |name: &str| {
+        let at = args.iter().position(|a| *a == name)?;
+        args.get(at + 1)?.parse::<u64>().ok()
+    };
+    let (time, inc) = match side {
+        chess::Color::White => (value("wtime"), value("winc")),
+        chess::Color::Black => (value("btime"), value("binc")),
+    };
+    if let Some(depth) = value("depth") {
+        Limit::Depth(u8::try_from(depth).unwrap_or(u8::MAX).max(1))
+    } else if let Some(ms) = value("movetime") {
+        Limit::Time(Duration::from_millis(ms))
+    } else if let Some(ms) = time {
+        Limit::Time(Duration::from_millis(ms / 30 + inc.unwrap_or(0)))
+    } else {
+        Limit::Depth(DEFAULT_DEPTH)
+    }
+}
+
+//==============================================================================
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::io::Cursor;
+
+    /// Output shared with the test, which reads it after the engine has written it
+    #[derive(Clone, Default)]
+    struct Shared(Arc<Mutex<Vec<u8>>>);
+    impl Write for Shared {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            self.0.lock().unwrap().write(buf)
+        }
+
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    /// Run the commands with the agent, returning the output lines
+    fn run(agent: &str, commands: &str) -> Vec<String> {
+        let output = Shared::default();
+        Uci::new(agent, output.clone())
+            .unwrap()
+            .run(Cursor::new(commands));
+        let text = String::from_utf8(output.0.lock().unwrap().clone()).unwrap();
+        text.lines().map(str::to_string).collect()
+    }
+
+    #[test]
+    fn handshake_and_search() {
+        let lines = run(
+            "smp",
+            "uci\nisready\nposition startpos moves e2e4 e7e5\ngo depth 3\n",
+        );
+        assert_eq!(lines[0], "id name chess_ai smp");
+        assert!(lines[1].starts_with("option name MultiPV "));
+        assert_eq!(lines[2], "option name Ponder type check default false");
+        assert_eq!(lines[3..5], ["uciok", "readyok"]);
+
+        // An info line for each depth, then a legal move and the expected reply
+        assert!(lines[5].starts_with("info depth 1 score cp "));
+        assert!(lines[6].starts_with("info depth 2 "));
+        assert!(lines[7].starts_with("info depth 3 "));
+        assert_eq!(lines.len(), 9);
+        let pv = lines[7].split(" pv ").nth(1).unwrap();
+        let (m, reply) = pv.split_once(' ').unwrap();
+        assert_eq!(
+            lines[8],
+            format!("bestmove {m} ponder {}", reply.split(' ').next().unwrap())
+        );
+        let m = ChessMove::from_str(m).unwrap();
+        assert!(position(&["startpos", "moves", "e2e4", "e7e5"])
+            .unwrap()
+            .1
+            .current_position()
+            .legal(m));
+
+        // Nothing after quit
+        assert!(run("smp", "quit\ngo depth 1\n").is_empty());
+    }
+
+    #[test]
+    fn mate_from_fen() {
+        let lines = run(
+            "depth",
+            "position fen 6k1/5ppp/8/8/8/8/5PPP/4R1K1 w - - 0 1\ngo movetime 10\n",
+        );
+        assert!(lines[0].starts_with("info depth 1 score mate 1 "));
+        assert_eq!(lines.last().unwrap(), "bestmove e1e8");
+
+        // Mated, with nothing to play
+        let lines = run(
+            "depth",
+            "position fen 6k1/5ppp/8/8/8/8/5PPP/4R1K1 w - - 0 1 moves e1e8\ngo\n",
+        );
+        assert_eq!(lines, ["bestmove 0000"]);
+    }
+
+    #[test]
+    fn multipv() {
+        let lines = run(
+            "depth",
+            "setoption name MultiPV value 3\nposition startpos\ngo depth 2\n",
+        );
+        assert_eq!(lines.len(), 4);
+        for (i, line) in lines[..3].iter().enumerate() {
+            assert!(line.starts_with(&format!("info multipv {} depth 2 score cp ", i + 1)));
+        }
+        let best = lines[0]
+            .split(" pv ")
+            .nth(1)
+            .unwrap()
+            .split(' ')
+            .next()
+            .unwrap();
+        assert!(lines[3].starts_with(&format!("bestmove {best} ponder ")));
+
+        // Lines at each depth within a time limit, and unknown options change nothing
+        let lines = run(
+            "smp",
+            "setoption name MultiPV value 2\nsetoption name Hash value 4\ngo movetime 1\n",
+        );
+        assert!(lines[0].starts_with("info multipv 1 depth 1 "));
+        assert!(lines[1].starts_with("info multipv 2 depth 1 "));
+        assert!(lines.last().unwrap().starts_with("bestmove "));
+    }
+
+    #[test]
+    fn ponder_and_stop() {
+        // Pondering waits for ponderhit however deep the limit, and a search until stop answers when stopped
+        let output = Shared::default();
+        let mut uci = Uci::new("smp", output.clone()).unwrap();
+        uci.command("setoption name Ponder value true");
+        let text = || String::from_utf8(output.0.lock().unwrap().clone()).unwrap();
+        for (go, answer) in [
+            ("go ponder depth 2", "ponderhit"),
+            ("go ponder movetime 1", "ponderhit"),
+            ("go infinite", "stop"),
+        ] {
+            output.0.lock().unwrap().clear();
+            uci.command("position startpos moves e2e4");
+            uci.command(go);
+            std::thread::sleep(Duration::from_millis(50));
+            uci.command("isready");
+            assert!(!text().contains("bestmove"), "{go}");
+            assert!(text().contains("readyok"));
+            uci.command(answer);
+            uci.wait();
+            let lines = text();
+            let best = lines.lines().find(|l| l.starts_with("bestmove ")).unwrap();
+            let m = ChessMove::from_str(best.split(' ').nth(1).unwrap()).unwrap();
+            assert!(position(&["startpos", "moves", "e2e4"])
+                .unwrap()
+                .1
+                .current_position()
+                .legal(m));
+        }
+
+        // A ponder miss is a stop, then a new search
+        let lines = run(
+            "depth",
+            "go ponder depth 2\nstop\nposition startpos moves d2d4\ngo depth 1\n",
+        );
+        assert_eq!(
+            lines.iter().filter(|l| l.starts_with("bestmove ")).count(),
+            2
+        );
+    }
+
+    #[test]
+    fn deadline() {
+        // A move time ends the search on time, however deep it would go
+        for agent in ["smp", "depth"] {
+            let start = std::time::Instant::now();
+            let lines = run(agent, "go movetime 200\n");
+            assert!(
+                start.elapsed() < Duration::from_millis(400),
+                "{agent} {:?}",
+                start.elapsed()
+            );
+            assert!(lines[0].starts_with("info depth 1 "));
+            assert!(lines.last().unwrap().starts_with("bestmove "));
+        }
+    }
+
+    #[test]
+    fn limits() {
+        assert!(Uci::new("nobody", Vec::new()).is_none());
+        assert!(position(&["startpos", "moves", "e2e5"]).is_none());
+        assert!(position(&["fen", "not", "a", "position"]).is_none());
+        let fen = [
+            "fen",
+            "7k/8/8/8/8/8/8/R6K",
+            "w",
+            "-",
+            "-",
+            "12",
+            "40",
+            "moves",
+            "a1b1",
+        ];
+        let (start, game) = position(&fen).unwrap();
+        assert_eq!((start.halfmove_clock, game.actions().len()), (12, 1));
+
+        let white = chess::Color::White;
+        assert!(matches!(go_limit(&["depth", "5"], white), Limit::Depth(5)));
+        assert!(matches!(go_limit(&[], white), Limit::Depth(DEFAULT_DEPTH)));
+        assert!(
+            matches!(go_limit(&["movetime", "250"], white), Limit::Time(t) if t.as_millis() == 250)
+        );
+        let clock = [
+            "wtime", "3000", "btime", "60000", "winc", "100", "binc", "0",
+        ];
+        assert!(matches!(go_limit(&clock, white), Limit::Time(t) if t.as_millis() == 200));
+        assert!(
+            matches!(go_limit(&clock, chess::Color::Black), Limit::Time(t) if t.as_millis() == 2000)
+        );
+    }
+}
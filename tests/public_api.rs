@@ -0,0 +1,145 @@
+use chess::{Board, ChessMove, Game, MoveGen, Square};
+use chess_ai::agent_mcts::Budget;
+use chess_ai::epd::{self, EpdPosition};
+use chess_ai::{
+    agent_by_name, play_game, play_game_logged, Agent, Calc, HumanAgent, Limit, Score, Thresholds,
+    Turn,
+};
+use chess_ai::{DepthAgent, MctsAgent, RandomAgent, SimpleAgent, SmpAgent, ThreadAgent};
+use chess_ai::{Evaluator, Material};
+use std::io::Cursor;
+
+/// An agent written outside the crate, always playing the first legal move.
+struct FirstMoveAgent;
+impl Agent for FirstMoveAgent {
+    fn make_move(&mut self, game: &Game) -> Option<ChessMove> {
+        MoveGen::new_legal(&game.current_position()).next()
+    }
+
+    fn name(&self) -> String {
+        "FirstMoveAgent".to_string()
+    }
+}
+
+#[test]
+fn every_agent_plays_a_legal_move() {
+    let agents: Vec<Box<dyn Agent>> = vec![
+        Box::new(RandomAgent::new()),
+        Box::new(SimpleAgent::new()),
+        Box::new(DepthAgent::new(2)),
+        Box::new(ThreadAgent::new(2)),
+        Box::new(SmpAgent::new(2)),
+        Box::new(MctsAgent::new(Budget::Iterations(100))),
+        Box::new(FirstMoveAgent),
+    ];
+    for mut agent in agents {
+        let mut game = Game::new();
+        assert!(
+            game.make_move(agent.make_move(&game).unwrap()),
+            "{}",
+            agent.name()
+        );
+        assert!(
+            game.make_move(agent.make_move(&game).unwrap()),
+            "{}",
+            agent.name()
+        );
+    }
+}
+
+#[test]
+fn match_runner() {
+    let wins = play_game(Box::new(SimpleAgent::new()), Box::new(FirstMoveAgent), 2);
+    assert_eq!(wins.iter().sum::<u32>(), 2);
+
+    // A search agent that resigns at any score loses every game
+    let resign = Thresholds {
+        resign: Some(Score::INFINITY),
+        draw: None,
+    };
+    let wins = play_game(
+        agent_by_name("depth", 1, resign).unwrap(),
+        Box::new(RandomAgent::new()),
+        3,
+    );
+    assert_eq!(wins, [0, 3, 0]);
+    let wins = play_game_logged(Box::new(SmpAgent::new(1)), Box::new(RandomAgent::new()), 1);
+    assert_eq!(wins.iter().sum::<u32>(), 1);
+    assert!(agent_by_name("nobody", 1, resign).is_none());
+}
+
+#[test]
+fn human_agent_from_script() {
+    let script = Cursor::new(b"e4\nresign\n".to_vec());
+    let mut human = HumanAgent::new(script, Vec::new(), false, None);
+    let game = Game::new();
+    assert_eq!(
+        human.take_turn(&game),
+        Turn::Move(ChessMove::new(Square::E2, Square::E4, None))
+    );
+    assert_eq!(human.take_turn(&game), Turn::Resign);
+}
+
+#[test]
+fn analyze_by_default() {
+    // An agent without a search reports only its move
+    let game = Game::new();
+    let lines = SimpleAgent::new().analyze(&game, 3);
+    assert_eq!(lines.len(), 1);
+    assert!(game.current_position().legal(lines[0].pv[0]));
+    assert_eq!(FirstMoveAgent.analyze(&game, 3)[0].score, Score::ZERO);
+
+    // A search agent ranks several
+    assert_eq!(DepthAgent::new(2).analyze(&game, 3).len(), 3);
+}
+
+#[test]
+fn calc_scores() {
+    let calc = Calc::new();
+    let board = Game::new().current_position();
+    let e2e4 = ChessMove::new(Square::E2, Square::E4, None);
+    assert_eq!(calc.score_board(&board, true), Score::ZERO);
+    assert_eq!(
+        calc.score_move(&board, true, e2e4, Score::ZERO),
+        Score::cp(40)
+    );
+    assert_eq!(
+        calc.score_board(&board.make_move_new(e2e4), true),
+        Score::cp(40)
+    );
+    assert_eq!(Score::mate_in(3).to_string(), "mate 2");
+}
+
+/// An evaluator written outside the crate, scoring how many moves the side to move has.
+struct Mobility;
+impl Evaluator for Mobility {
+    fn score_board(&self, board: &Board, is_white: bool) -> Score {
+        let moves = MoveGen::new_legal(board).len() as i32;
+        let to_move = (board.side_to_move() == chess::Color::White) == is_white;
+        Score::cp(if to_move { moves } else { -moves })
+    }
+}
+
+#[test]
+fn evaluators() {
+    let mut depth = DepthAgent::new(2);
+    depth.set_evaluator(Box::new(Mobility));
+    let mut smp = SmpAgent::new(2);
+    smp.set_evaluator(Box::new(Material));
+    let wins = play_game(Box::new(depth), Box::new(smp), 1);
+    assert_eq!(wins.iter().sum::<u32>(), 1);
+}
+
+#[test]
+fn epd_suite() {
+    let positions =
+        vec![
+            EpdPosition::parse(r#"6k1/5ppp/8/8/8/8/5PPP/4R1K1 w - - bm Re8#; id "mate";"#).unwrap(),
+        ];
+    let result = epd::run_suite(
+        &|d| Box::new(DepthAgent::new(d)),
+        &positions,
+        Limit::Depth(1),
+    );
+    assert_eq!(result.solved(), 1);
+}